@@ -1,5 +1,9 @@
+use std::str::FromStr;
+
 pub type TransitionFunction<T, S> = fn(&mut Vec<Vec<T>>, &mut S);
-pub type InitFunction<T, S> = fn(usize, usize) -> (Vec<Vec<T>>, S);
+pub type InitFunction<T, S> = fn(usize, usize, &mut S) -> Vec<Vec<T>>;
+// Called with the row, the column and the index of one of the brushes of GlobalState::brushes.
+pub type PaintFunction<T, S> = fn(&mut Vec<Vec<T>>, &mut S, usize, usize, usize);
 
 // A cellular automaton, whose state consists of:
 // * n by m grid of elements of type T
@@ -11,29 +15,30 @@ pub struct Automaton<T, S> {
     global_state: S,
     init_fn: InitFunction<T, S>,
     next_fn: TransitionFunction<T, S>,
+    paint_fn: Option<PaintFunction<T, S>>,
 }
 
 impl<T, S> Automaton<T, S> {
-    // Creates a new automaton with the given grid size, init and transition function.
+    // Creates a new automaton with the given grid size, global state, init and transition function.
+    // The init function creates the initial grid and may reset the parts of the global state that
+    // only belong to a single run (parameters stored in the global state are kept on reset).
     // The transition function computes the next state of the automaton given mutable references to the
     // grid and global state.
-    pub fn new(n: usize, m: usize, init_fn: InitFunction<T, S>, next_fn: TransitionFunction<T, S>) -> Automaton<T, S> {
-        let (grid, global_state) = init_fn(n, m);
+    pub fn new(n: usize, m: usize, mut global_state: S, init_fn: InitFunction<T, S>, next_fn: TransitionFunction<T, S>) -> Automaton<T, S> {
+        let grid = init_fn(n, m, &mut global_state);
         Automaton {
             grid: grid,
             global_state: global_state,
             init_fn: init_fn,
             next_fn: next_fn,
+            paint_fn: None,
         }
     }
-    
-    // Returns (height, width) of the grid
-    pub fn size(&self) -> (usize, usize) {
-        let n = self.grid.len();
-        if n == 0 {
-            return (0, 0);
-        }
-        return (n, self.grid[0].len())
+
+    // Lets the user paint on the grid with the given function, see GlobalState::brushes.
+    pub fn with_paint_fn(mut self, paint_fn: PaintFunction<T, S>) -> Automaton<T, S> {
+        self.paint_fn = Some(paint_fn);
+        self
     }
 
     // Returns the current grid and global state
@@ -47,41 +52,260 @@ impl<T, S> Automaton<T, S> {
     }
 
     pub fn reset(&mut self, n: usize, m: usize) {
-        let (grid, global_state) = (self.init_fn)(n, m);
-        self.grid = grid;
-        self.global_state = global_state;
+        self.grid = (self.init_fn)(n, m, &mut self.global_state);
+    }
+
+    // Paints the cell in row i and column j with the given brush, if the automaton supports painting.
+    pub fn paint(&mut self, i: usize, j: usize, brush: usize) {
+        if let Some(paint_fn) = self.paint_fn {
+            paint_fn(&mut self.grid, &mut self.global_state, i, j, brush);
+        }
+    }
+}
+
+impl<T, S: GlobalState> Automaton<T, S> {
+    pub fn set_param(&mut self, name: &str, value: f64) {
+        self.global_state.set_param(name, value);
+    }
+}
+
+// A parameter of an automaton that can be changed from the user interface.
+pub struct Param {
+    pub name: String,
+    pub value: f64,
+    pub kind: ParamKind,
+}
+
+pub enum ParamKind {
+    // integer value in the given (inclusive) range
+    Int(i64, i64),
+    // floating point value in the given (inclusive) range
+    Float(f64, f64),
+    // index into the given list of options
+    Choice(Vec<String>),
+}
+
+impl Param {
+    pub fn int(name: &str, value: i64, min: i64, max: i64) -> Param {
+        Param { name: name.to_string(), value: value as f64, kind: ParamKind::Int(min, max) }
+    }
+
+    pub fn float(name: &str, value: f64, min: f64, max: f64) -> Param {
+        Param { name: name.to_string(), value: value, kind: ParamKind::Float(min, max) }
+    }
+
+    pub fn choice(name: &str, value: usize, options: &[&str]) -> Param {
+        let options = options.iter().map(|o| o.to_string()).collect();
+        Param { name: name.to_string(), value: value as f64, kind: ParamKind::Choice(options) }
+    }
+}
+
+// The global state S of an automaton can implement this trait to describe itself
+// and to expose parameters that can be changed while the simulation is running.
+pub trait GlobalState {
+    // Short description of the automaton shown in the user interface, e.g. "Rule 30"
+    fn description(&self) -> String {
+        String::new()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        Vec::new()
+    }
+
+    // Called with the name of one of the parameters returned by params() and its new value.
+    fn set_param(&mut self, _name: &str, _value: f64) {}
+
+    // Named values describing the current state, e.g. the density of live cells.
+    fn stats(&self) -> Vec<(String, f64)> {
+        Vec::new()
+    }
+
+    // Names of the brushes the user can paint on the grid with, if the automaton has a paint function.
+    fn brushes(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl GlobalState for () {}
+
+// Writes `row` into the spacetime diagram `grid` at index `*next` and advances `*next`.
+// This is used by automata whose grid shows the history of a one-dimensional state,
+// once the grid is full the oldest row is dropped so the diagram scrolls upward.
+pub fn append_row<T>(grid: &mut Vec<Vec<T>>, next: &mut usize, row: Vec<T>) {
+    let n = grid.len();
+    if *next >= n {
+        grid.rotate_left(1);
+        *next = n - 1;
+    }
+    grid[*next] = row;
+    *next += 1;
+}
+
+// The cells around a cell that influence its next state.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Neighborhood {
+    // all cells in the (2k+1) square centered at the cell
+    Moore(usize),
+    // all cells with manhattan distance at most k
+    VonNeumann(usize),
+}
+
+impl Neighborhood {
+    // Returns the offsets (di, dj) of all neighbors, excluding (0, 0).
+    pub fn offsets(&self) -> Vec<(isize, isize)> {
+        let k = match self {
+            Neighborhood::Moore(k) | Neighborhood::VonNeumann(k) => *k as isize,
+        };
+        let mut result = Vec::new();
+        for di in -k..=k {
+            for dj in -k..=k {
+                if (di, dj) == (0, 0) {
+                    continue;
+                }
+                if let Neighborhood::VonNeumann(_) = self {
+                    if di.abs() + dj.abs() > k {
+                        continue;
+                    }
+                }
+                result.push((di, dj));
+            }
+        }
+        return result;
+    }
+
+    pub fn with_radius(&self, k: usize) -> Neighborhood {
+        match self {
+            Neighborhood::Moore(_) => Neighborhood::Moore(k),
+            Neighborhood::VonNeumann(_) => Neighborhood::VonNeumann(k),
+        }
+    }
+}
+
+// Neighborhoods are given as "moore" or "von-neumann" with radius 1.
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Neighborhood, String> {
+        match s {
+            "moore" => Ok(Neighborhood::Moore(1)),
+            "von-neumann" => Ok(Neighborhood::VonNeumann(1)),
+            _ => Err(format!("unknown neighborhood '{}', expected 'moore' or 'von-neumann'", s)),
+        }
+    }
+}
+
+// Transition functions get the grid by mutable reference, so besides computing the next grid from
+// the current one (synchronous update) they can also change the cells one after the other (in-place
+// update), which lets particles move through the grid. The result of an in-place update depends on
+// the order of the cells, ScanOrder and UpdateMarks help to keep it unbiased.
+
+// Order in which an in-place transition function visits the cells of a grid.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ScanOrder {
+    pub bottom_up: bool,
+    pub right_to_left: bool,
+}
+
+impl ScanOrder {
+    // Scans the rows in the given direction and alternates the direction of the columns every step,
+    // so that over time neither side is preferred.
+    pub fn alternating(bottom_up: bool, step: u64) -> ScanOrder {
+        ScanOrder { bottom_up: bottom_up, right_to_left: step % 2 == 1 }
     }
+
+    // Returns all cells (i, j) of an n by m grid in this order.
+    pub fn cells(&self, n: usize, m: usize) -> Vec<(usize, usize)> {
+        let mut result = Vec::with_capacity(n * m);
+        for r in 0..n {
+            let i = if self.bottom_up { n - 1 - r } else { r };
+            for c in 0..m {
+                let j = if self.right_to_left { m - 1 - c } else { c };
+                result.push((i, j));
+            }
+        }
+        return result;
+    }
+}
+
+// Marks the cells that were already updated in the current step of an in-place update, e.g. so
+// that a particle that moved in scan direction is not moved again when its new cell is visited.
+pub struct UpdateMarks {
+    marks: Vec<Vec<u64>>,
+    step: u64,
 }
 
-// Returns all the cells in a (2k+1) square grid centered at the cell (x,y).
+impl UpdateMarks {
+    pub fn new(n: usize, m: usize) -> UpdateMarks {
+        UpdateMarks { marks: vec![vec![0; m]; n], step: 1 }
+    }
+
+    // Clears all marks, call this at the start of every step.
+    pub fn next_step(&mut self) {
+        self.step += 1;
+    }
+
+    pub fn mark(&mut self, i: usize, j: usize) {
+        self.marks[i][j] = self.step;
+    }
+
+    pub fn is_marked(&self, i: usize, j: usize) -> bool {
+        self.marks[i][j] == self.step
+    }
+}
+
+// Returns the cell at offset (di, dj) from (i, j) on an n by m torus.
+pub fn wrap(i: usize, j: usize, di: isize, dj: isize, n: usize, m: usize) -> (usize, usize) {
+    let wi = (i as isize + di).rem_euclid(n as isize) as usize;
+    let wj = (j as isize + dj).rem_euclid(m as isize) as usize;
+    (wi, wj)
+}
+
+// Returns all the cells in a (2k+1) square grid centered at the cell (x,y),
+// excluding the cell itself and cells outside the grid.
 pub fn neighbors<T>(x: usize, y: usize, v: &Vec<Vec<T>>, k: usize) -> Vec<&T> {
     let mut result = Vec::new();
 
-    if v.len() == 0 {
+    if v.is_empty() {
         return result;
     }
 
     let n = v.len();
     let m = v[0].len();
 
-    for i in 0..k+1 {
-        for j in 0..k+1 {
-            if i == 0 && j == 0 {
+    for i in x.saturating_sub(k)..(x+k+1).min(n) {
+        for j in y.saturating_sub(k)..(y+k+1).min(m) {
+            if i == x && j == y {
                 continue;
             }
-            if x >= i && y >= j {
-                result.push(&v[x-i][y-j]);
-            }
-            if x+i < n && y+j < m {
-                result.push(&v[x+i][y+j]);
-            }
-            if x >= i && y+j < m {
-                result.push(&v[x-i][y+j]);
-            }
-            if x+i < n && y >= j {
-                result.push(&v[x+i][y-j]);
-            }
+            result.push(&v[i][j]);
         }
     }
     return result;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 5 by 5 grid where every cell holds its own index
+    fn grid() -> Vec<Vec<usize>> {
+        (0..5).map(|i| (0..5).map(|j| i * 5 + j).collect()).collect()
+    }
+
+    #[test]
+    fn neighbors_counts_every_cell_once() {
+        let v = grid();
+        let mut cells: Vec<usize> = neighbors(2, 2, &v, 1).into_iter().copied().collect();
+        cells.sort();
+        assert_eq!(cells, vec![6, 7, 8, 11, 13, 16, 17, 18]);
+        assert_eq!(neighbors(2, 2, &v, 2).len(), 24);
+    }
+
+    #[test]
+    fn neighbors_at_the_border() {
+        let v = grid();
+        let mut cells: Vec<usize> = neighbors(0, 0, &v, 1).into_iter().copied().collect();
+        cells.sort();
+        assert_eq!(cells, vec![1, 5, 6]);
+    }
+}
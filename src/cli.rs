@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::{env, process};
+
+// Command line arguments of the form `<automaton> [--option value]...`
+pub struct Args {
+    pub automaton: String,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    // Parses the arguments the program was started with.
+    // If no automaton is given, `default` is used.
+    pub fn from_env(default: &str) -> Args {
+        let mut automaton = default.to_string();
+        let mut options = HashMap::new();
+
+        let mut args = env::args().skip(1).peekable();
+        if let Some(a) = args.peek() {
+            if !a.starts_with("--") {
+                automaton = a.clone();
+                args.next();
+            }
+        }
+        while let Some(a) = args.next() {
+            match a.strip_prefix("--") {
+                Some(key) => match args.next() {
+                    Some(value) => { options.insert(key.to_string(), value); },
+                    None => exit_with_error(&format!("missing value for option --{}", key)),
+                },
+                None => exit_with_error(&format!("unexpected argument '{}'", a)),
+            }
+        }
+
+        Args {
+            automaton: automaton,
+            options: options,
+        }
+    }
+
+    // Returns the value of the given option or `default` if it was not set.
+    // Exits the program if the value cannot be parsed.
+    pub fn get<T: FromStr>(&self, key: &str, default: T) -> T
+    where T::Err: std::fmt::Display {
+        self.get_optional(key).unwrap_or(default)
+    }
+
+    // Returns the value of the given option if it was set.
+    // Exits the program if the value cannot be parsed.
+    pub fn get_optional<T: FromStr>(&self, key: &str) -> Option<T>
+    where T::Err: std::fmt::Display {
+        let value = self.options.get(key)?;
+        match value.parse() {
+            Ok(v) => Some(v),
+            Err(e) => exit_with_error(&format!("invalid value '{}' for option --{}: {}", value, key, e)),
+        }
+    }
+}
+
+pub fn exit_with_error(message: &str) -> ! {
+    println!("error: {}", message);
+    process::exit(1);
+}
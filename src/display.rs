@@ -2,6 +2,7 @@ use eframe::egui::{self, Ui};
 use std::{sync::mpsc::{channel, Receiver, Sender}, thread};
 use crate::simulation::{SimulationState, Command};
 use crate::image::{FlatImg, fit_image_size};
+use crate::automaton::ParamKind;
 
 pub fn run(
     recv: Receiver<SimulationState<FlatImg>>,
@@ -28,6 +29,8 @@ struct UiState {
     delay_value: u64,
     height_slider_value: usize,
     width_slider_value: usize,
+    // index of the selected brush, see SimulationState::brushes
+    brush: usize,
 }
 
 
@@ -58,13 +61,17 @@ impl MyApp {
             simulation_state: SimulationState {
                 step: 0,
                 running: false,
+                description: String::new(),
+                params: vec![],
+                stats: vec![],
+                brushes: vec![],
                 data: FlatImg {
                     img: vec![],
                     width: 0,
                     height: 0,
                 }
             },
-            ui_state: UiState { delay_value: 0, height_slider_value: 10, width_slider_value: 10 }
+            ui_state: UiState { delay_value: 0, height_slider_value: 10, width_slider_value: 10, brush: 0 }
         }
     }
 }
@@ -73,6 +80,9 @@ impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.recv_simulation_state();
 
+        egui::SidePanel::right("side_panel").show(ctx, |ui| {
+            self.build_side_panel(ui);
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.build_image(ui, _frame);
@@ -125,11 +135,88 @@ impl MyApp {
             egui::TextureOptions::NEAREST,
         );
         // TODO do we need to scale these values for aspect ratios
-        ui.image(&texture, image_size);
+        let painting = !self.simulation_state.brushes.is_empty();
+        let sense = if painting { egui::Sense::click_and_drag() } else { egui::Sense::hover() };
+        let response = ui.add(egui::Image::new(&texture, image_size).sense(sense));
+
+        // paint the cell under the pointer, pixels of the image are cells of the grid
+        if painting && (response.clicked() || response.dragged()) {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let rect = response.rect;
+                let x = (pos.x - rect.min.x) / rect.width() * flat_img.width as f32;
+                let y = (pos.y - rect.min.y) / rect.height() * flat_img.height as f32;
+                if x >= 0.0 && y >= 0.0 && (x as usize) < flat_img.width && (y as usize) < flat_img.height {
+                    self.send_command(Command::Paint(y as usize, x as usize, self.ui_state.brush));
+                }
+            }
+        }
+    }
+
+    // Shows the description of the automaton, controls for its parameters and its statistics.
+    // Changed values are sent to the simulation immediately, the displayed values
+    // are always the ones reported by the simulation.
+    fn build_side_panel(&mut self, ui: &mut Ui) {
+        if !self.simulation_state.description.is_empty() {
+            ui.heading(&self.simulation_state.description);
+        }
+        let brushes = &self.simulation_state.brushes;
+        if !brushes.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Brush:");
+                egui::ComboBox::from_id_source("brush")
+                    .selected_text(brushes.get(self.ui_state.brush).cloned().unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for (i, brush) in brushes.iter().enumerate() {
+                            ui.selectable_value(&mut self.ui_state.brush, i, brush);
+                        }
+                    });
+            });
+        }
+        for param in &self.simulation_state.params {
+            let mut value = param.value;
+            let changed = ui.horizontal(|ui| {
+                ui.label(format!("{}:", param.name));
+                match &param.kind {
+                    ParamKind::Int(min, max) => {
+                        ui.add(egui::Slider::new(&mut value, *min as f64..=*max as f64).integer()).changed()
+                    },
+                    ParamKind::Float(min, max) => {
+                        ui.add(egui::Slider::new(&mut value, *min..=*max)).changed()
+                    },
+                    ParamKind::Choice(options) => {
+                        let mut selected = value as usize;
+                        let mut changed = false;
+                        egui::ComboBox::from_id_source(&param.name)
+                            .selected_text(options.get(selected).cloned().unwrap_or_default())
+                            .show_ui(ui, |ui| {
+                                for (i, option) in options.iter().enumerate() {
+                                    changed |= ui.selectable_value(&mut selected, i, option).changed();
+                                }
+                            });
+                        value = selected as f64;
+                        changed
+                    },
+                }
+            }).inner;
+            if changed {
+                self.send_command(Command::SetParam(param.name.clone(), value));
+            }
+        }
+        if !self.simulation_state.stats.is_empty() {
+            ui.separator();
+            egui::Grid::new("stats").show(ui, |ui| {
+                for (name, value) in &self.simulation_state.stats {
+                    ui.label(name);
+                    ui.label(format_stat(*value));
+                    ui.end_row();
+                }
+            });
+        }
     }
 
     fn build_controls(&mut self, ui: &mut Ui) {
-        ui.label(format!("Step: {}", self.simulation_state.step));
+        let status = if self.simulation_state.running { "running" } else { "stopped" };
+        ui.label(format!("Step: {} ({})", self.simulation_state.step, status));
         if ui.button("Start").clicked() {
             self.send_command(Command::Start);
         };
@@ -162,4 +249,12 @@ impl MyApp {
             };
         });
     }
-}
\ No newline at end of file
+}
+
+fn format_stat(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.4}", value)
+    }
+}
@@ -0,0 +1,318 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re: re, im: im }
+    }
+
+    // Returns e^(i phi).
+    fn from_angle(phi: f64) -> Complex {
+        Complex { re: phi.cos(), im: phi.sin() }
+    }
+
+    fn conj(&self) -> Complex {
+        Complex { re: self.re, im: -self.im }
+    }
+
+    fn scale(&self, s: f64) -> Complex {
+        Complex { re: self.re * s, im: self.im * s }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex { re: self.re + other.re, im: self.im + other.im }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex { re: self.re - other.re, im: self.im - other.im }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+enum Algorithm {
+    // iterative Cooley-Tukey for lengths that are powers of two, with the twiddle factors
+    // e^(-2 pi i k / n) for k < n/2
+    Radix2(Vec<Complex>),
+    // Bluestein's algorithm for all other lengths, computing the transform as a convolution of
+    // power of two length with the chirp e^(-pi i k^2 / n)
+    Bluestein {
+        chirp: Vec<Complex>,
+        // transform of the conjugated chirp, padded and wrapped around to the inner length
+        filter: Vec<Complex>,
+        inner: Box<FftPlan>,
+    },
+}
+
+// A discrete Fourier transform of a fixed length, with all the values that only depend on the
+// length computed once.
+pub struct FftPlan {
+    n: usize,
+    algorithm: Algorithm,
+}
+
+impl FftPlan {
+    pub fn new(n: usize) -> FftPlan {
+        if n.is_power_of_two() {
+            let twiddles = (0..n / 2).map(|k| Complex::from_angle(-2.0 * PI * k as f64 / n as f64)).collect();
+            return FftPlan { n: n, algorithm: Algorithm::Radix2(twiddles) };
+        }
+
+        let inner = FftPlan::new((2 * n - 1).next_power_of_two());
+        let len = inner.n;
+        // k^2 is reduced modulo 2n to keep the angles small and precise
+        let chirp: Vec<Complex> = (0..n)
+            .map(|k| Complex::from_angle(-PI * ((k * k) % (2 * n)) as f64 / n as f64))
+            .collect();
+        let mut filter = vec![Complex::default(); len];
+        filter[0] = chirp[0].conj();
+        for k in 1..n {
+            filter[k] = chirp[k].conj();
+            filter[len - k] = chirp[k].conj();
+        }
+        inner.forward(&mut filter);
+        FftPlan {
+            n: n,
+            algorithm: Algorithm::Bluestein { chirp: chirp, filter: filter, inner: Box::new(inner) },
+        }
+    }
+
+    // Replaces the data of the plan's length with its discrete Fourier transform
+    // X_k = sum_j x_j e^(-2 pi i j k / n).
+    pub fn forward(&self, data: &mut [Complex]) {
+        match &self.algorithm {
+            Algorithm::Radix2(twiddles) => radix2(data, twiddles),
+            Algorithm::Bluestein { chirp, filter, inner } => {
+                let mut a = vec![Complex::default(); inner.n];
+                for k in 0..self.n {
+                    a[k] = data[k] * chirp[k];
+                }
+                inner.forward(&mut a);
+                for k in 0..a.len() {
+                    a[k] = a[k] * filter[k];
+                }
+                inner.inverse(&mut a);
+                for k in 0..self.n {
+                    data[k] = a[k] * chirp[k];
+                }
+            },
+        }
+    }
+
+    // Replaces the data with its inverse discrete Fourier transform, including the factor 1/n.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        for c in data.iter_mut() {
+            *c = c.conj();
+        }
+        self.forward(data);
+        let scale = 1.0 / self.n as f64;
+        for c in data.iter_mut() {
+            *c = c.conj().scale(scale);
+        }
+    }
+}
+
+fn radix2(data: &mut [Complex], twiddles: &Vec<Complex>) {
+    let n = data.len();
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let stride = n / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let w = twiddles[k * stride];
+                let u = data[start + k];
+                let v = data[start + k + len / 2] * w;
+                data[start + k] = u + v;
+                data[start + k + len / 2] = u - v;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Two-dimensional discrete Fourier transforms of n by m grids, used to compute convolutions on
+// the torus with large kernels in O(nm log(nm)) instead of O(nm k^2).
+pub struct Fft2 {
+    n: usize,
+    m: usize,
+    rows: FftPlan,
+    cols: FftPlan,
+}
+
+impl Fft2 {
+    pub fn new(n: usize, m: usize) -> Fft2 {
+        Fft2 { n: n, m: m, rows: FftPlan::new(m), cols: FftPlan::new(n) }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.n, self.m)
+    }
+
+    fn apply(&self, x: &mut Vec<Vec<Complex>>, inverse: bool) {
+        for row in x.iter_mut() {
+            if inverse { self.rows.inverse(row) } else { self.rows.forward(row) }
+        }
+        let mut column = vec![Complex::default(); self.n];
+        for j in 0..self.m {
+            for i in 0..self.n {
+                column[i] = x[i][j];
+            }
+            if inverse { self.cols.inverse(&mut column) } else { self.cols.forward(&mut column) }
+            for i in 0..self.n {
+                x[i][j] = column[i];
+            }
+        }
+    }
+
+    // Returns the transform of a real grid.
+    pub fn transform(&self, x: &Vec<Vec<f32>>) -> Vec<Vec<Complex>> {
+        let mut result: Vec<Vec<Complex>> = x.iter()
+            .map(|row| row.iter().map(|v| Complex::new(*v as f64, 0.0)).collect())
+            .collect();
+        self.apply(&mut result, false);
+        return result;
+    }
+
+    // Returns the transform of a kernel given by its value at every offset (di, dj), where offsets
+    // are taken in -n/2..n/2 and -m/2..m/2 so kernels larger than the grid wrap around.
+    pub fn kernel_transform<F: Fn(isize, isize) -> f64>(&self, kernel: F) -> Vec<Vec<Complex>> {
+        let (n, m) = (self.n as isize, self.m as isize);
+        let mut result = vec![vec![Complex::default(); self.m]; self.n];
+        for i in 0..n {
+            for j in 0..m {
+                let di = if i <= n / 2 { i } else { i - n };
+                let dj = if j <= m / 2 { j } else { j - m };
+                result[i as usize][j as usize] = Complex::new(kernel(di, dj), 0.0);
+            }
+        }
+        self.apply(&mut result, false);
+        return result;
+    }
+
+    // Returns the transform of the kernel scaled to sum 1 over the offsets of the torus, so that
+    // kernels larger than the grid are normalized as well. The transform at frequency zero is the
+    // sum of the kernel.
+    pub fn normalized_kernel_transform<F: Fn(isize, isize) -> f64>(&self, kernel: F) -> Vec<Vec<Complex>> {
+        let mut result = self.kernel_transform(kernel);
+        let total = result[0][0].re;
+        for c in result.iter_mut().flatten() {
+            *c = c.scale(1.0 / total);
+        }
+        return result;
+    }
+
+    // Returns the convolution sum_d kernel(d) x(p - d) at every cell p of the torus, given the
+    // transforms of the grid and the kernel.
+    pub fn convolve(&self, x: &Vec<Vec<Complex>>, kernel: &Vec<Vec<Complex>>) -> Vec<Vec<f64>> {
+        let mut product: Vec<Vec<Complex>> = x.iter().zip(kernel.iter())
+            .map(|(a, b)| a.iter().zip(b.iter()).map(|(u, v)| *u * *v).collect())
+            .collect();
+        self.apply(&mut product, true);
+        product.iter().map(|row| row.iter().map(|c| c.re).collect()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(n: usize) -> Vec<Complex> {
+        (0..n).map(|k| Complex::new((k as f64 * 0.7).sin() + 0.1 * k as f64, (k as f64 * 1.3).cos())).collect()
+    }
+
+    fn naive_dft(x: &[Complex]) -> Vec<Complex> {
+        let n = x.len();
+        (0..n).map(|k| {
+            x.iter().enumerate().fold(Complex::default(), |sum, (j, v)| {
+                sum + *v * Complex::from_angle(-2.0 * PI * ((j * k) % n) as f64 / n as f64)
+            })
+        }).collect()
+    }
+
+    fn assert_close(a: &[Complex], b: &[Complex]) {
+        assert_eq!(a.len(), b.len());
+        for (u, v) in a.iter().zip(b.iter()) {
+            assert!((u.re - v.re).abs() < 1e-9 && (u.im - v.im).abs() < 1e-9, "({}, {}) != ({}, {})", u.re, u.im, v.re, v.im);
+        }
+    }
+
+    #[test]
+    fn forward_matches_naive_dft() {
+        // powers of two use radix 2, the other lengths Bluestein's algorithm
+        for n in [1, 2, 8, 64, 3, 5, 12, 100] {
+            let x = signal(n);
+            let mut y = x.clone();
+            FftPlan::new(n).forward(&mut y);
+            assert_close(&y, &naive_dft(&x));
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_forward() {
+        for n in [16, 7, 30] {
+            let x = signal(n);
+            let mut y = x.clone();
+            let plan = FftPlan::new(n);
+            plan.forward(&mut y);
+            plan.inverse(&mut y);
+            assert_close(&y, &x);
+        }
+    }
+
+    #[test]
+    fn convolution_on_the_torus() {
+        let (n, m) = (6, 10);
+        let x: Vec<Vec<f32>> = (0..n).map(|i| (0..m).map(|j| ((i * m + j) % 7) as f32).collect()).collect();
+        let kernel = |di: isize, dj: isize| if di.abs() <= 1 && dj.abs() <= 1 { (2 + di + 3 * dj) as f64 } else { 0.0 };
+        let fft = Fft2::new(n, m);
+        let result = fft.convolve(&fft.transform(&x), &fft.kernel_transform(kernel));
+        for i in 0..n {
+            for j in 0..m {
+                let mut expected = 0.0;
+                for di in -1..=1 {
+                    for dj in -1..=1 {
+                        let (pi, pj) = ((i as isize - di).rem_euclid(n as isize), (j as isize - dj).rem_euclid(m as isize));
+                        expected += kernel(di, dj) * x[pi as usize][pj as usize] as f64;
+                    }
+                }
+                assert!((result[i][j] - expected).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -9,12 +9,17 @@ pub struct FlatImg {
 
 impl FlatImg {
 	pub fn from_2d_vec<T: ToNum>(v: &Vec<Vec<T>>) -> FlatImg {
+		FlatImg::from_2d_vec_with(v, |x| num_to_rgb(x.to_num()))
+	}
+
+	// Creates an image using the given function to compute the color of every element.
+	pub fn from_2d_vec_with<T, F: Fn(&T) -> [u8; 3]>(v: &Vec<Vec<T>>, color: F) -> FlatImg {
 		let n = v.len();
 		let m = v[0].len();
 		let mut img = vec![0; n*m*3];
 		for i in 0..n {
 			for j in 0..m {
-				let rgb = num_to_rgb(v[i][j].to_num());
+				let rgb = color(&v[i][j]);
 				let index = (i*m + j) * 3;
 				img[index] = rgb[0];
 				img[index + 1] = rgb[1];
@@ -42,7 +47,34 @@ impl ToNum for u8 {
 	}
 }
 
-fn num_to_rgb(t: u64) -> [u8; 3] {
+// Returns the color between `from` (t = 0) and `to` (t = 1).
+pub fn interpolate(from: [u8; 3], to: [u8; 3], t: f32) -> [u8; 3] {
+	let t = t.clamp(0.0, 1.0);
+	let mut rgb = [0; 3];
+	for i in 0..3 {
+		rgb[i] = (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8;
+	}
+	rgb
+}
+
+// Colors of the colormap at t = 0, 0.25, 0.5, 0.75 and 1, close to viridis.
+const COLORMAP: [[u8; 3]; 5] = [
+	[68, 1, 84],
+	[59, 82, 139],
+	[33, 145, 140],
+	[94, 201, 98],
+	[253, 231, 37],
+];
+
+// Returns the color of a continuous value t in [0, 1] (values outside are clamped), from dark
+// purple over blue and green to yellow. Used for automata whose cells are not discrete states.
+pub fn colormap(t: f32) -> [u8; 3] {
+	let t = t.clamp(0.0, 1.0) * (COLORMAP.len() - 1) as f32;
+	let i = (t.floor() as usize).min(COLORMAP.len() - 2);
+	interpolate(COLORMAP[i], COLORMAP[i + 1], t - i as f32)
+}
+
+pub fn num_to_rgb(t: u64) -> [u8; 3] {
     match t {
         0 => [255,255,255],
         1 => [0,0,0],
@@ -1,25 +1,510 @@
+// The code base prefers explicit returns, explicit field initialization and index based loops.
+#![allow(clippy::needless_return, clippy::redundant_field_names, clippy::needless_range_loop, clippy::ptr_arg, clippy::single_match)]
+
 use std::{thread, time};
-use simulation::Simulation;
+use simulation::{Simulation, TransformFunction};
 use image::{FlatImg, ToNum};
-use crate::rules::{one_dim::new_rule30_automaton, one_dim::new_rule184_automaton ,game_of_life::new_gol_automaton, multi_type::new_multi_type_automaton};
+use automaton::{Automaton, GlobalState};
+use crate::rules::{one_dim, nasch, bml, multi_type, spatial_game, cyclic, forest_fire, sandpile, ising, schelling, epidemic, wator, turmite, wireworld, falling_sand, reaction_diffusion, lenia, lattice_gas, lattice_boltzmann, growth, game_of_life::new_gol_automaton};
 
 mod automaton;
+mod cli;
 mod display;
 mod rng;
 mod image;
+mod fft;
 mod simulation;
+mod statistics;
 mod rules;
 
+const USAGE: &str = "usage: automata [gol | elementary | one-dim | rule30 | rule184 | traffic-diagram | nasch | bml | multi-type | game | cyclic | rps | forest-fire | sandpile | ising | schelling | epidemic | wator | turmite | wireworld | falling-sand | gray-scott | lenia | smoothlife | lattice-gas | lbm | dla | eden] [--option value]...
+
+common options:
+  --height <rows>         height of the grid (default 200)
+  --width <columns>       width of the grid (default 200)
+  --delay <ms>            delay after each step (default 50)
+
+one-dimensional automata (elementary, one-dim, rule30, rule184) scroll once the grid is full:
+  --history <rows>        number of time steps shown (default: the height)
+
+elementary:
+  --rule <0-255>          Wolfram code of the rule (default 30)
+  --init <middle|random>  initial row (default middle)
+  --density <0-1>         fraction of non-zero cells in a random initial row (default 0.5)
+  --boundary <periodic|fixed0|fixed1|reflective|expanding>
+                          cells beyond the edges (default expanding for middle, periodic for random)
+
+one-dim:
+  --states <2-10>         number of states k (default 3)
+  --radius <r>            neighborhood radius (default 1)
+  --kind <table|totalistic|outer-totalistic>
+                          how neighborhoods are mapped to the rule table (default totalistic)
+  --code <n>              Wolfram code of the rule (default 777)
+  --digits <d...>         all digits of the code, most significant first, instead of --code
+  --init <middle|random>  initial row (default middle)
+  --density <0-1>         as for elementary
+  --boundary <...>        as for elementary
+
+rule184 (traffic flow, reports density, flux and average velocity):
+  --density <0-1>         initial car density (default 0.5)
+
+traffic-diagram (prints the fundamental diagram of rule184 as CSV instead of opening a window):
+  --width <cells>         length of the road (default 1000)
+  --steps <n>             steps per measurement (default 1000)
+  --points <n>            number of initial densities (default 51)
+
+nasch (Nagel-Schreckenberg traffic model, scrolls like the one-dimensional automata):
+  --lanes <1-4>           number of lanes (default 1)
+  --vmax <1-10>           maximum velocity (default 5)
+  --p <0-1>               probability of random braking (default 0.3)
+  --density <0-1>         initial car density (default 0.2)
+  --seed <n>              seed of the random number generator (default: random)
+
+bml (Biham-Middleton-Levine traffic on a torus, jams above a density of about 0.35):
+  --density <0-1>         initial car density (default 0.3)
+  --seed <n>              seed of the random number generator (default: random)
+
+multi-type:
+  --types <2-10>          number of types (default 10)
+  --radius <r>            radius of the neighborhood (default 4)
+  --noise <n>             scores are perturbed by a random value in [-n, n] (default 5)
+  --tie-break <random|lowest|keep>
+                          choice between equally common neighbor types (default random)
+  --drift <d>             random change of every score per step (default 0)
+  --inherit <true|false>  converted cells inherit the score of the best cell of their new type
+                          instead of keeping their own (default false)
+  --mutation <d>          random change of an inherited score (default 0)
+  --decay <0-1>           fraction every score decays per step (default 0)
+  --seed <n>              seed of the random number generator (default: random)
+
+game (spatial game, by default the Prisoner's Dilemma of Nowak and May on a torus):
+  --b <b>                 temptation to defect of the Prisoner's Dilemma (default 1.85)
+  --payoff <matrix>       payoff matrix with rows separated by ';', e.g. '1,0;1.85,0'
+                          (default: the Prisoner's Dilemma with temptation b)
+  --imitation <best|fermi>
+                          imitate the best neighbor or a random one with the Fermi rule (default best)
+  --neighborhood <moore|von-neumann>
+                          neighbors every cell plays against (default moore)
+  --radius <r>            radius of the neighborhood (default 1)
+  --initial <0-1>         initial fraction of strategy 0 (default 0.9)
+  --seed <n>              seed of the random number generator (default: random)
+
+cyclic (cyclic cellular automaton on a torus):
+  --states <2-10>         number of states N (default 8)
+  --threshold <n>         neighbors in the successor state needed to advance (default 1)
+  --neighborhood <moore|von-neumann>
+                          (default von-neumann)
+  --radius <r>            radius of the neighborhood (default 1)
+  --seed <n>              seed of the random number generator (default: random)
+
+rps (May-Leonard rock-paper-scissors model on a torus):
+  --species <3-9>         number of species (default 3)
+  --sigma <rate>          selection rate (default 1)
+  --mu <rate>             reproduction rate (default 1)
+  --epsilon <rate>        exchange rate, controls the mobility (default 3)
+  --seed <n>              seed of the random number generator (default: random)
+
+forest-fire (Drossel-Schwabl model on a torus, reports fire sizes and tree clusters):
+  --p <0-1>               probability that a tree grows on an empty cell (default 0.01)
+  --f <0-1>               probability that lightning strikes a tree (default 0.00005)
+  --density <0-1>         initial fraction of cells with trees (default 0.5)
+  --seed <n>              seed of the random number generator (default: random)
+
+sandpile (Abelian sandpile, reports avalanche size and duration distributions):
+  --step <grain|toppling> a step adds a grain and relaxes the pile or topples once (default grain)
+  --drop <center|random>  where grains are added (default center)
+  --start <empty|random>  initial heights (default empty)
+  --seed <n>              seed of the random number generator (default: random)
+
+ising (2D Ising model on a torus, reports magnetization and energy per spin):
+  --temperature <T>       temperature, the critical temperature is 2.269 (default 2.0)
+  --field <h>             external magnetic field (default 0)
+  --dynamics <metropolis|glauber|checkerboard>
+                          spin update rule (default metropolis)
+  --seed <n>              seed of the random number generator (default: random)
+
+schelling (Schelling segregation model, reports the segregation index):
+  --types <2-9>           number of agent types (default 2)
+  --empty <0-1>           fraction of empty cells (default 0.1)
+  --tolerance <0-1>       fraction of like neighbors an agent needs to stay (default 0.5)
+  --radius <r>            radius of the Moore neighborhood (default 1)
+  --move <random|happy>   unhappy agents move to a random empty cell or to one where they
+                          would be happy (default random)
+  --seed <n>              seed of the random number generator (default: random)
+
+epidemic (SIR/SEIR epidemic on a torus):
+  --beta <0-1>            per-contact infection probability per step (default 0.2)
+  --incubation <steps>    steps exposed before becoming infectious, 0 for SIR (default 3)
+  --recovery <steps>      steps infectious before recovering (default 7)
+  --vaccinated <0-1>      fraction of cells immune from the start (default 0)
+  --infected <n>          number of infected cells at the start (default 5)
+  --neighborhood <moore|von-neumann>
+                          (default moore)
+  --radius <r>            radius of the neighborhood (default 1)
+  --output <file>         write the population of every state after each step as CSV
+  --seed <n>              seed of the random number generator (default: random)
+
+wator (Wa-Tor predator-prey model on a torus, plots the populations below the ocean):
+  --fish-breed <steps>    steps after which a fish reproduces (default 3)
+  --shark-breed <steps>   steps after which a shark reproduces (default 10)
+  --shark-energy <e>      energy of a newborn shark, sharks lose 1 per step (default 3)
+  --fish-energy <e>       energy a shark gains by eating a fish (default 3)
+  --fish <0-1>            initial fraction of cells with fish (default 0.3)
+  --sharks <0-1>          initial fraction of cells with sharks (default 0.05)
+  --output <file>         write the fish and shark populations after each step as CSV
+  --seed <n>              seed of the random number generator (default: random)
+
+turmite (Langton's ant and turmites on a torus, ants are shown in magenta):
+  --rule <rule>           turns per color as letters L, R, N (none) or U, e.g. RL or LLRR,
+                          or a turmite table like {{{1,2,0},{0,8,0}}} (default RL)
+  --ants <n>              number of ants (default 1)
+  --moves <n>             moves of every ant per step (default 10)
+  --seed <n>              seed of the random number generator for placing further ants (default: random)
+
+wireworld (Wireworld, cells and components like diodes, clocks and gates can be painted with the mouse):
+  --start <empty|demo>    start with an empty grid or a demo circuit (default demo)
+
+falling-sand (sand, water, stone, fire and smoke, materials can be painted with the mouse):
+  --start <empty|scene>   start with an empty grid or a scene of stone, sand and water (default scene)
+  --seed <n>              seed of the random number generator (default: random)
+
+gray-scott (Gray-Scott reaction-diffusion on a torus, shows the concentration of V):
+  --preset <spots|stripes|mitosis|coral>
+                          feed and kill rates of a known pattern (default spots)
+  --feed <F>              feed rate, overrides the preset
+  --kill <k>              kill rate, overrides the preset
+  --du <D>                diffusion rate of U (default 1)
+  --dv <D>                diffusion rate of V (default 0.5)
+  --iterations <n>        integration steps per step (default 10)
+  --seed <n>              seed of the random number generator (default: random)
+
+lenia (Lenia on a torus, continuous states with a smooth ring kernel):
+  --preset <orbium|large-orbium|swarm|scutium|soup>
+                          initial pattern and the parameters it lives in (default orbium)
+  --radius <R>            kernel radius (default 13, 26 for large-orbium)
+  --peaks <h1,h2,...>     heights of the concentric rings of the kernel (default 1)
+  --time-resolution <T>   updates per unit of time (default 10)
+  --mu <mu>               center of the growth function (default 0.15, 0.29 for scutium)
+  --sigma <sigma>         width of the growth function (default 0.015, 0.043 for scutium)
+  --seed <n>              seed of the random number generator (default: random)
+
+smoothlife (SmoothLife on a torus, a continuous Game of Life):
+  --radius <r>            radius of the outer ring, the inner disk has a third of it (default 21)
+  --birth <b1,b2>         birth interval of the ring filling (default 0.278,0.365)
+  --death <d1,d2>         survival interval of the ring filling (default 0.267,0.445)
+  --alpha-n <a>           smoothness of the steps in the ring filling (default 0.028)
+  --alpha-m <a>           smoothness of the step in the inner filling (default 0.147)
+  --dt <dt>               time step, 1 for discrete updates (default 1)
+  --seed <n>              seed of the random number generator (default: random)
+
+lattice-gas (HPP or FHP lattice gas on a torus, walls and gas can be painted with the mouse):
+  --model <hpp|fhp>       square grid with four or hexagonal grid with six directions (default fhp)
+  --start <flow|shock>    a channel with a plate in the flow, or a square of dense gas (default flow)
+  --density <0-1>         probability that a direction of a cell is occupied (default 0.2)
+  --drive <0-1>           probability per cell and step of turning a particle from west to east (default 0.01)
+  --block <n>             width of the blocks the velocity field is averaged over (default 8)
+  --seed <n>              seed of the random number generator (default: random)
+
+lbm (D2Q9 lattice Boltzmann fluid flowing from left to right, obstacles can be painted with the mouse):
+  --viscosity <nu>        kinematic viscosity in lattice units, greater than 0 (default 0.02)
+  --velocity <u>          inflow velocity in lattice units, at most 0.2 (default 0.1)
+  --iterations <n>        time steps of the fluid per step (default 10)
+  --start <cylinder|plate|empty>
+                          obstacle in the flow at the start (default cylinder)
+  --view <speed|vorticity|density>
+                          quantity shown (default vorticity)
+
+dla (diffusion-limited aggregation from a particle in the middle, colored by attachment time):
+  --stickiness <p>        probability in (0, 1] that a walker next to the cluster sticks to it (default 1)
+  --particles <n>         particles attached per step (default 10)
+  --seed <n>              seed of the random number generator (default: random)
+
+eden (Eden growth from a particle in the middle, colored by attachment time):
+  --particles <n>         particles attached per step (default 50)
+  --seed <n>              seed of the random number generator (default: random)";
+
 fn main() {
-    let n = 200;
-    let m = 200;
+    let args = cli::Args::from_env("gol");
+    let n = args.get("height", 200);
+    let m = args.get("width", 200);
+    let delay = time::Duration::from_millis(args.get("delay", 50));
+    let history = args.get("history", n);
 
-    let aut = new_gol_automaton(n, m);
-    // let aut = new_rule30_automaton(n);
-    // let aut = new_rule184_automaton(n, m);
-    // let aut = new_multi_type_automaton(n, m);
+    match args.automaton.as_str() {
+        "gol" => run(new_gol_automaton(n, m), transform, delay),
+        "elementary" => {
+            let rule = args.get("rule", 30);
+            let initial = initial_condition(&args);
+            let boundary = args.get("boundary", one_dim::default_boundary(initial));
+            run(one_dim::new_elementary_automaton(history, m, rule, initial, boundary), transform, delay)
+        },
+        "one-dim" => {
+            let states = args.get("states", 3);
+            let radius = args.get("radius", 1);
+            let kind = args.get("kind", one_dim::RuleKind::Totalistic);
+            let digits: String = args.get("digits", String::new());
+            let rule = if digits.is_empty() {
+                one_dim::Rule::from_code(states, radius, kind, args.get("code", 777))
+            } else {
+                one_dim::Rule::from_digits(states, radius, kind, &digits)
+            };
+            let rule = rule.unwrap_or_else(|e| cli::exit_with_error(&e));
+            let initial = initial_condition(&args);
+            let boundary = args.get("boundary", one_dim::default_boundary(initial));
+            run(one_dim::new_one_dim_automaton(history, m, rule, initial, boundary), transform, delay)
+        },
+        "rule30" => run(one_dim::new_rule30_automaton(history), transform, delay),
+        "rule184" => run(one_dim::new_rule184_automaton(history, m, args.get("density", 0.5)), transform, delay),
+        "traffic-diagram" => {
+            let diagram = one_dim::fundamental_diagram(args.get("width", 1000), args.get("steps", 1000), args.get("points", 51));
+            println!("density,flux,velocity");
+            for (density, flux, velocity) in diagram {
+                println!("{},{},{}", density, flux, velocity);
+            }
+        },
+        "nasch" => {
+            let aut = nasch::new_nasch_automaton(
+                history, m,
+                args.get("lanes", 1),
+                args.get("vmax", 5),
+                args.get("p", 0.3),
+                args.get("density", 0.2),
+                args.get_optional("seed"),
+            );
+            run(aut, nasch::transform, delay)
+        },
+        "bml" => run(bml::new_bml_automaton(n, m, args.get("density", 0.3), args.get_optional("seed")), transform, delay),
+        "multi-type" => {
+            let defaults = multi_type::MultiTypeParams::default();
+            let params = multi_type::MultiTypeParams {
+                types: args.get("types", defaults.types),
+                radius: args.get("radius", defaults.radius),
+                noise: args.get("noise", defaults.noise),
+                tie_break: args.get("tie-break", defaults.tie_break),
+                drift: args.get("drift", defaults.drift),
+                inherit: args.get("inherit", defaults.inherit),
+                mutation: args.get("mutation", defaults.mutation),
+                decay: args.get("decay", defaults.decay),
+            };
+            if params.radius < 1 {
+                cli::exit_with_error("the radius of the neighborhood must be at least 1");
+            }
+            run(multi_type::new_multi_type_automaton(n, m, params, args.get_optional("seed")), transform, delay)
+        },
+        "game" => {
+            let payoff = match args.get_optional::<String>("payoff") {
+                Some(p) => spatial_game::parse_payoff(&p).unwrap_or_else(|e| cli::exit_with_error(&e)),
+                None => spatial_game::prisoners_dilemma(args.get("b", 1.85)),
+            };
+            let aut = spatial_game::new_game_automaton(
+                n, m, payoff,
+                neighborhood(&args, automaton::Neighborhood::Moore(1)),
+                args.get("imitation", spatial_game::Imitation::Best),
+                args.get("initial", 0.9),
+                args.get_optional("seed"),
+            );
+            run(aut, spatial_game::transform, delay)
+        },
+        "cyclic" => {
+            let neighborhood = neighborhood(&args, automaton::Neighborhood::VonNeumann(1));
+            let aut = cyclic::new_cyclic_automaton(n, m, args.get("states", 8), args.get("threshold", 1), neighborhood, args.get_optional("seed"));
+            run(aut, transform, delay)
+        },
+        "rps" => {
+            let aut = cyclic::new_rps_automaton(
+                n, m,
+                args.get("species", 3),
+                args.get("sigma", 1.0),
+                args.get("mu", 1.0),
+                args.get("epsilon", 3.0),
+                args.get_optional("seed"),
+            );
+            run(aut, transform, delay)
+        },
+        "forest-fire" => {
+            let aut = forest_fire::new_forest_fire_automaton(
+                n, m,
+                args.get("p", 0.01),
+                args.get("f", 0.00005),
+                args.get("density", 0.5),
+                args.get_optional("seed"),
+            );
+            run(aut, transform, delay)
+        },
+        "sandpile" => {
+            let aut = sandpile::new_sandpile_automaton(
+                n, m,
+                args.get("step", sandpile::StepMode::Grain),
+                args.get("drop", sandpile::Placement::Center),
+                args.get::<String>("start", "empty".to_string()) == "random",
+                args.get_optional("seed"),
+            );
+            run(aut, transform, delay)
+        },
+        "ising" => {
+            let aut = ising::new_ising_automaton(
+                n, m,
+                args.get("temperature", 2.0),
+                args.get("field", 0.0),
+                args.get("dynamics", ising::Dynamics::Metropolis),
+                args.get_optional("seed"),
+            );
+            run(aut, transform, delay)
+        },
+        "schelling" => {
+            let defaults = schelling::SchellingParams::default();
+            let params = schelling::SchellingParams {
+                types: args.get("types", defaults.types),
+                empty: args.get("empty", defaults.empty),
+                tolerance: args.get("tolerance", defaults.tolerance),
+                radius: args.get("radius", defaults.radius),
+                relocation: args.get("move", defaults.relocation),
+            };
+            run(schelling::new_schelling_automaton(n, m, params, args.get_optional("seed")), transform, delay)
+        },
+        "epidemic" => {
+            let defaults = epidemic::EpidemicParams::default();
+            let params = epidemic::EpidemicParams {
+                beta: args.get("beta", defaults.beta),
+                incubation: args.get("incubation", defaults.incubation),
+                recovery: args.get("recovery", defaults.recovery),
+                neighborhood: neighborhood(&args, automaton::Neighborhood::Moore(1)),
+                vaccinated: args.get("vaccinated", defaults.vaccinated),
+                initial_infected: args.get("infected", defaults.initial_infected),
+            };
+            run(epidemic::new_epidemic_automaton(n, m, params, args.get_optional("output"), args.get_optional("seed")), transform, delay)
+        },
+        "wator" => {
+            let defaults = wator::WatorParams::default();
+            let params = wator::WatorParams {
+                fish_breed: args.get("fish-breed", defaults.fish_breed),
+                shark_breed: args.get("shark-breed", defaults.shark_breed),
+                shark_energy: args.get("shark-energy", defaults.shark_energy),
+                fish_energy: args.get("fish-energy", defaults.fish_energy),
+                fish_density: args.get("fish", defaults.fish_density),
+                shark_density: args.get("sharks", defaults.shark_density),
+            };
+            run(wator::new_wator_automaton(n, m, params, args.get_optional("output"), args.get_optional("seed")), wator::transform, delay)
+        },
+        "turmite" => {
+            let aut = turmite::new_turmite_automaton(
+                n, m,
+                args.get("rule", turmite::TurmiteRule::langton()),
+                args.get("ants", 1),
+                args.get("moves", 10),
+                args.get_optional("seed"),
+            );
+            run(aut, turmite::transform, delay)
+        },
+        "wireworld" => {
+            let demo = args.get::<String>("start", "demo".to_string()) == "demo";
+            run(wireworld::new_wireworld_automaton(n, m, demo), transform, delay)
+        },
+        "falling-sand" => {
+            let scene = args.get::<String>("start", "scene".to_string()) == "scene";
+            run(falling_sand::new_falling_sand_automaton(n, m, scene, args.get_optional("seed")), falling_sand::transform, delay)
+        },
+        "gray-scott" => {
+            let defaults = reaction_diffusion::GrayScottParams::from_preset(args.get("preset", reaction_diffusion::Preset::Spots));
+            let params = reaction_diffusion::GrayScottParams {
+                feed: args.get("feed", defaults.feed),
+                kill: args.get("kill", defaults.kill),
+                du: args.get("du", defaults.du),
+                dv: args.get("dv", defaults.dv),
+                iterations: args.get("iterations", defaults.iterations),
+            };
+            run(reaction_diffusion::new_gray_scott_automaton(n, m, params, args.get_optional("seed")), reaction_diffusion::transform, delay)
+        },
+        "lenia" => {
+            let preset = args.get("preset", lenia::Preset::Orbium);
+            let defaults = lenia::LeniaParams::from_preset(preset);
+            let peaks = match args.get_optional::<String>("peaks") {
+                Some(peaks) => lenia::parse_peaks(&peaks).unwrap_or_else(|e| cli::exit_with_error(&e)),
+                None => defaults.peaks,
+            };
+            let params = lenia::LeniaParams {
+                radius: args.get("radius", defaults.radius),
+                time_resolution: args.get("time-resolution", defaults.time_resolution),
+                mu: args.get("mu", defaults.mu),
+                sigma: args.get("sigma", defaults.sigma),
+                peaks: peaks,
+            };
+            run(lenia::new_lenia_automaton(n, m, params, preset, args.get_optional("seed")), lenia::transform, delay)
+        },
+        "smoothlife" => {
+            let defaults = lenia::SmoothLifeParams::default();
+            let interval = |key: &str, default: (f64, f64)| match args.get_optional::<String>(key) {
+                Some(s) => lenia::parse_interval(&s).unwrap_or_else(|e| cli::exit_with_error(&e)),
+                None => default,
+            };
+            let params = lenia::SmoothLifeParams {
+                radius: args.get("radius", defaults.radius),
+                birth: interval("birth", defaults.birth),
+                death: interval("death", defaults.death),
+                alpha_n: args.get("alpha-n", defaults.alpha_n),
+                alpha_m: args.get("alpha-m", defaults.alpha_m),
+                dt: args.get("dt", defaults.dt),
+            };
+            run(lenia::new_smooth_life_automaton(n, m, params, args.get_optional("seed")), lenia::transform, delay)
+        },
+        "lattice-gas" => {
+            let defaults = lattice_gas::LatticeGasParams::default();
+            let params = lattice_gas::LatticeGasParams {
+                model: args.get("model", defaults.model),
+                density: args.get("density", defaults.density),
+                drive: args.get("drive", defaults.drive),
+                scene: args.get("start", defaults.scene),
+                block: args.get("block", defaults.block),
+            };
+            run(lattice_gas::new_lattice_gas_automaton(n, m, params, args.get_optional("seed")), lattice_gas::transform, delay)
+        },
+        "lbm" => {
+            let defaults = lattice_boltzmann::LatticeBoltzmannParams::default();
+            let params = lattice_boltzmann::LatticeBoltzmannParams {
+                viscosity: args.get("viscosity", defaults.viscosity),
+                inflow: args.get("velocity", defaults.inflow),
+                iterations: args.get("iterations", defaults.iterations),
+                scene: args.get("start", defaults.scene),
+                view: args.get("view", defaults.view),
+            };
+            if !(params.viscosity > 0.0 && params.viscosity.is_finite()) {
+                cli::exit_with_error("the viscosity must be greater than 0");
+            }
+            if !(params.inflow >= 0.0 && params.inflow <= 0.2) {
+                cli::exit_with_error("the inflow velocity must be between 0 and 0.2");
+            }
+            run(lattice_boltzmann::new_lattice_boltzmann_automaton(n, m, params), lattice_boltzmann::transform, delay)
+        },
+        "dla" => {
+            let aut = growth::new_dla_automaton(n, m, args.get("stickiness", 1.0), args.get("particles", 10), args.get_optional("seed"));
+            let aut = aut.unwrap_or_else(|e| cli::exit_with_error(&e));
+            run(aut, growth::transform, delay)
+        },
+        "eden" => run(growth::new_eden_automaton(n, m, args.get("particles", 50), args.get_optional("seed")), growth::transform, delay),
+        "help" => println!("{}", USAGE),
+        other => cli::exit_with_error(&format!("unknown automaton '{}'\n{}", other, USAGE)),
+    }
+}
 
-    let delay = time::Duration::from_millis(50);
+fn initial_condition(args: &cli::Args) -> one_dim::InitialCondition {
+    match args.get("init", one_dim::InitialCondition::Middle) {
+        one_dim::InitialCondition::Random(_) => one_dim::InitialCondition::Random(args.get("density", 0.5)),
+        initial => initial,
+    }
+}
+
+// Returns the neighborhood given by --neighborhood and --radius, or the default shape.
+fn neighborhood(args: &cli::Args, default: automaton::Neighborhood) -> automaton::Neighborhood {
+    let radius = args.get("radius", 1);
+    if radius < 1 {
+        cli::exit_with_error("the radius of the neighborhood must be at least 1");
+    }
+    args.get("neighborhood", default).with_radius(radius)
+}
+
+// Runs the automaton in a separate thread and shows it until the window is closed.
+fn run<T, S>(aut: Automaton<T, S>, transform: TransformFunction<T, S, FlatImg>, delay: time::Duration)
+where T: Send + 'static, S: Send + GlobalState + 'static {
     let (mut simulation, output_recv, command_send) = Simulation::new(
         aut,
         transform,
@@ -32,6 +517,6 @@ fn main() {
     display::run(output_recv, command_send);
 }
 
-fn transform<T: ToNum,S>(grid: &Vec<Vec<T>>, global_state: &S) -> FlatImg {
+fn transform<T: ToNum,S>(grid: &Vec<Vec<T>>, _global_state: &S) -> FlatImg {
     FlatImg::from_2d_vec(grid)
-}
\ No newline at end of file
+}
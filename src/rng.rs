@@ -1,12 +1,25 @@
-use rand::{distributions::Uniform, prelude::Distribution};
+use rand::{distributions::Uniform, prelude::Distribution, rngs::StdRng, SeedableRng};
 
 pub struct UniformRng {
-	rng: rand::rngs::ThreadRng,
+	rng: StdRng,
 }
 	
 impl UniformRng {
 	pub fn new() -> UniformRng {
-		UniformRng { rng: rand::thread_rng() }
+		UniformRng { rng: StdRng::from_entropy() }
+	}
+
+	// Returns a generator that always produces the same sequence for the same seed
+	pub fn with_seed(seed: u64) -> UniformRng {
+		UniformRng { rng: StdRng::seed_from_u64(seed) }
+	}
+
+	// Returns a generator seeded with the given seed or from entropy if there is none
+	pub fn from_seed(seed: Option<u64>) -> UniformRng {
+		match seed {
+			Some(s) => UniformRng::with_seed(s),
+			None => UniformRng::new(),
+		}
 	}
 
 	// Returns a random element in the range from low (inclusive) to high (exclusive)
@@ -14,4 +27,16 @@ impl UniformRng {
         let between = Uniform::from(low..high);
 		between.sample(&mut self.rng)
 	}
-}
\ No newline at end of file
+
+	// Returns true with probability p
+	pub fn chance(&mut self, p: f64) -> bool {
+		self.sample(0.0, 1.0) < p
+	}
+
+	// Shuffles the elements into a uniformly random order
+	pub fn shuffle<T>(&mut self, v: &mut [T]) {
+		for k in (1..v.len()).rev() {
+			v.swap(k, self.sample(0, k + 1));
+		}
+	}
+}
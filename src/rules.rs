@@ -1,3 +1,21 @@
 pub mod multi_type;
 pub mod game_of_life;
-pub mod one_dim;
\ No newline at end of file
+pub mod one_dim;
+pub mod nasch;
+pub mod bml;
+pub mod spatial_game;
+pub mod cyclic;
+pub mod forest_fire;
+pub mod sandpile;
+pub mod ising;
+pub mod schelling;
+pub mod epidemic;
+pub mod wator;
+pub mod turmite;
+pub mod wireworld;
+pub mod falling_sand;
+pub mod reaction_diffusion;
+pub mod lenia;
+pub mod lattice_gas;
+pub mod lattice_boltzmann;
+pub mod growth;
@@ -0,0 +1,131 @@
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::image::ToNum;
+use crate::rng;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    // a car moving to the right
+    East,
+    // a car moving down
+    South,
+}
+
+impl ToNum for Cell {
+    // white, red and blue in the palette of num_to_rgb
+    fn to_num(&self) -> u64 {
+        match self {
+            Cell::Empty => 0,
+            Cell::East => 2,
+            Cell::South => 4,
+        }
+    }
+}
+
+// Global state of the Biham-Middleton-Levine traffic model.
+// Every step of the automaton is a half-step in which only one kind of car moves,
+// east- and south-moving cars take turns.
+pub struct BmlState {
+    // initial fraction of occupied cells, half of the cars move east and half move south
+    density: f64,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // true if the east-moving cars move in the next step
+    east_next: bool,
+    east_cars: usize,
+    south_cars: usize,
+    // number of cars that moved in the last half-step of each kind
+    east_moved: usize,
+    south_moved: usize,
+}
+
+impl GlobalState for BmlState {
+    fn description(&self) -> String {
+        "Biham-Middleton-Levine".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![Param::float("Density", self.density, 0.0, 1.0)]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        // used when the automaton is restarted
+        if name == "Density" {
+            self.density = value;
+        }
+    }
+
+    // The average velocity drops to 0 in the jammed phase above a critical density.
+    fn stats(&self) -> Vec<(String, f64)> {
+        let cars = self.east_cars + self.south_cars;
+        let velocity = if cars > 0 { (self.east_moved + self.south_moved) as f64 / cars as f64 } else { 0.0 };
+        vec![
+            ("Cars".to_string(), cars as f64),
+            ("Average velocity".to_string(), velocity),
+        ]
+    }
+}
+
+// Creates the model on an n by m torus. If no seed is given, the random number generator is seeded from entropy.
+pub fn new_bml_automaton(n: usize, m: usize, density: f64, seed: Option<u64>) -> Automaton<Cell, BmlState> {
+    let state = BmlState {
+        density: density,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        east_next: true,
+        east_cars: 0,
+        south_cars: 0,
+        east_moved: 0,
+        south_moved: 0,
+    };
+    Automaton::new(n, m, state, init_random, next_fn)
+}
+
+fn init_random(n: usize, m: usize, state: &mut BmlState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![Cell::Empty; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            if state.rng.chance(state.density) {
+                *cell = if state.rng.chance(0.5) { Cell::East } else { Cell::South };
+            }
+        }
+    }
+    state.east_next = true;
+    state.east_cars = grid.iter().flatten().filter(|c| **c == Cell::East).count();
+    state.south_cars = grid.iter().flatten().filter(|c| **c == Cell::South).count();
+    state.east_moved = 0;
+    state.south_moved = 0;
+    return grid;
+}
+
+// Moves all cars of one kind forward by one cell if that cell is empty.
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut BmlState) {
+    let n = x.len();
+    let m = x[0].len();
+    let (kind, di, dj) = if y.east_next { (Cell::East, 0, 1) } else { (Cell::South, 1, 0) };
+
+    let mut next = x.clone();
+    let mut moved = 0;
+    for i in 0..n {
+        for j in 0..m {
+            if x[i][j] != kind {
+                continue;
+            }
+            let (ti, tj) = ((i + di) % n, (j + dj) % m);
+            if x[ti][tj] == Cell::Empty {
+                next[i][j] = Cell::Empty;
+                next[ti][tj] = kind;
+                moved += 1;
+            }
+        }
+    }
+    *x = next;
+
+    if y.east_next {
+        y.east_moved = moved;
+    } else {
+        y.south_moved = moved;
+    }
+    y.east_next = !y.east_next;
+}
@@ -0,0 +1,230 @@
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param, wrap};
+use crate::rng;
+
+const NEIGHBORHOOD_OPTIONS: [&str; 2] = ["Moore", "von Neumann"];
+
+// Returns the fraction of cells in each of the given number of states.
+fn frequencies(x: &Vec<Vec<u8>>, states: usize) -> Vec<f64> {
+    let mut counts = vec![0; states];
+    for v in x.iter().flatten() {
+        if (*v as usize) < states {
+            counts[*v as usize] += 1;
+        }
+    }
+    let total = counts.iter().sum::<usize>().max(1) as f64;
+    counts.iter().map(|c| *c as f64 / total).collect()
+}
+
+// Global state of the cyclic cellular automaton: a cell in state k advances to state k+1 (mod N)
+// if at least `threshold` of its neighbors are in state k+1. From random initial states this
+// grows into spiral waves.
+pub struct CyclicState {
+    // number of states N, cells are only initialized to all states when the automaton is restarted
+    states: u8,
+    threshold: usize,
+    neighborhood: Neighborhood,
+    seed: Option<u64>,
+    frequencies: Vec<f64>,
+}
+
+impl GlobalState for CyclicState {
+    fn description(&self) -> String {
+        format!("Cyclic cellular automaton with {} states", self.states)
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let (kind, radius) = match self.neighborhood {
+            Neighborhood::Moore(k) => (0, k),
+            Neighborhood::VonNeumann(k) => (1, k),
+        };
+        vec![
+            Param::int("States", self.states as i64, 2, 10),
+            Param::int("Threshold", self.threshold as i64, 1, 24),
+            Param::choice("Neighborhood", kind, &NEIGHBORHOOD_OPTIONS),
+            Param::int("Radius", radius as i64, 1, 5),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "States" => self.states = value as u8,
+            "Threshold" => self.threshold = value as usize,
+            "Neighborhood" => {
+                let radius = match self.neighborhood {
+                    Neighborhood::Moore(k) | Neighborhood::VonNeumann(k) => k,
+                };
+                self.neighborhood = if value as usize == 1 { Neighborhood::VonNeumann(radius) } else { Neighborhood::Moore(radius) };
+            },
+            "Radius" => self.neighborhood = self.neighborhood.with_radius(value as usize),
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        self.frequencies.iter().enumerate()
+            .map(|(k, f)| (format!("State {}", k), *f))
+            .collect()
+    }
+}
+
+// Creates the cyclic cellular automaton with the given number of states (at most 10, the number
+// of colors in the palette) on an n by m torus.
+pub fn new_cyclic_automaton(n: usize, m: usize, states: u8, threshold: usize, neighborhood: Neighborhood, seed: Option<u64>) -> Automaton<u8, CyclicState> {
+    let state = CyclicState {
+        states: states.clamp(2, 10),
+        threshold: threshold,
+        neighborhood: neighborhood,
+        seed: seed,
+        frequencies: vec![],
+    };
+    Automaton::new(n, m, state, init_cyclic, cyclic_next_fn)
+}
+
+fn init_cyclic(n: usize, m: usize, state: &mut CyclicState) -> Vec<Vec<u8>> {
+    let mut rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            *cell = rng.sample(0, state.states);
+        }
+    }
+    state.frequencies = frequencies(&grid, state.states as usize);
+    return grid;
+}
+
+fn cyclic_next_fn(x: &mut Vec<Vec<u8>>, y: &mut CyclicState) {
+    let n = x.len();
+    let m = x[0].len();
+    let offsets = y.neighborhood.offsets();
+
+    let mut next = x.clone();
+    for i in 0..n {
+        for j in 0..m {
+            let successor = (x[i][j] + 1) % y.states;
+            let count = offsets.iter()
+                .filter(|(di, dj)| {
+                    let (ni, nj) = wrap(i, j, *di, *dj, n, m);
+                    x[ni][nj] == successor
+                })
+                .count();
+            if count >= y.threshold {
+                next[i][j] = successor;
+            }
+        }
+    }
+    *x = next;
+    y.frequencies = frequencies(x, y.states as usize);
+}
+
+// Global state of the stochastic rock-paper-scissors model of May and Leonard with mobility
+// (Reichenbach, Mobilia and Frey). Cells are empty (0) or occupied by one of the species 1..=S,
+// species k preys on species k+1 and species S preys on species 1.
+//
+// Every step is a Monte Carlo sweep of n*m interactions between a random cell and a random
+// von Neumann neighbor:
+// * selection (rate sigma): if the cell preys on the neighbor, the neighbor dies
+// * reproduction (rate mu): if the neighbor is empty, the cell reproduces into it
+// * exchange (rate epsilon): the cell and the neighbor swap places
+// Low mobility leads to spiral waves, high mobility to the extinction of all but one species.
+pub struct RpsState {
+    // number of species, cells are only initialized to all species when the automaton is restarted
+    species: u8,
+    sigma: f64,
+    mu: f64,
+    epsilon: f64,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    frequencies: Vec<f64>,
+}
+
+impl GlobalState for RpsState {
+    fn description(&self) -> String {
+        format!("May-Leonard model with {} species", self.species)
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("Species", self.species as i64, 3, 9),
+            Param::float("Selection rate", self.sigma, 0.0, 5.0),
+            Param::float("Reproduction rate", self.mu, 0.0, 5.0),
+            Param::float("Exchange rate", self.epsilon, 0.0, 20.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Species" => self.species = value as u8,
+            "Selection rate" => self.sigma = value,
+            "Reproduction rate" => self.mu = value,
+            "Exchange rate" => self.epsilon = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        self.frequencies.iter().enumerate()
+            .map(|(k, f)| if k == 0 { ("Empty".to_string(), *f) } else { (format!("Species {}", k), *f) })
+            .collect()
+    }
+}
+
+// Creates the May-Leonard model with the given number of species (3 is rock-paper-scissors,
+// at most 9 so every species has its own color) on an n by m torus.
+pub fn new_rps_automaton(n: usize, m: usize, species: u8, sigma: f64, mu: f64, epsilon: f64, seed: Option<u64>) -> Automaton<u8, RpsState> {
+    let state = RpsState {
+        species: species.clamp(3, 9),
+        sigma: sigma,
+        mu: mu,
+        epsilon: epsilon,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        frequencies: vec![],
+    };
+    Automaton::new(n, m, state, init_rps, rps_next_fn)
+}
+
+fn init_rps(n: usize, m: usize, state: &mut RpsState) -> Vec<Vec<u8>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            *cell = state.rng.sample(0, state.species + 1);
+        }
+    }
+    state.frequencies = frequencies(&grid, state.species as usize + 1);
+    return grid;
+}
+
+fn rps_next_fn(x: &mut Vec<Vec<u8>>, y: &mut RpsState) {
+    let n = x.len();
+    let m = x[0].len();
+    let total = y.sigma + y.mu + y.epsilon;
+    if total <= 0.0 {
+        return;
+    }
+    let offsets = Neighborhood::VonNeumann(1).offsets();
+    let species = y.species;
+    let rng = &mut y.rng;
+
+    for _ in 0..n*m {
+        let (i, j) = (rng.sample(0, n), rng.sample(0, m));
+        let (di, dj) = offsets[rng.sample(0, offsets.len())];
+        let (ni, nj) = wrap(i, j, di, dj, n, m);
+        let (a, b) = (x[i][j], x[ni][nj]);
+
+        let r = rng.sample(0.0, total);
+        if r < y.sigma {
+            if a != 0 && b == a % species + 1 {
+                x[ni][nj] = 0;
+            }
+        } else if r < y.sigma + y.mu {
+            if a != 0 && b == 0 {
+                x[ni][nj] = a;
+            }
+        } else {
+            x[i][j] = b;
+            x[ni][nj] = a;
+        }
+    }
+    y.frequencies = frequencies(x, species as usize + 1);
+}
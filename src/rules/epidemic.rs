@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::Write;
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param, wrap};
+use crate::image::ToNum;
+use crate::rng;
+
+const NEIGHBORHOOD_OPTIONS: [&str; 2] = ["Moore", "von Neumann"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Susceptible,
+    // infected but not yet infectious, for the given number of further steps
+    Exposed(u32),
+    // infectious, for the given number of further steps
+    Infected(u32),
+    Recovered,
+    Vaccinated,
+}
+
+impl ToNum for Cell {
+    // white, yellow, red, green and blue in the palette of num_to_rgb
+    fn to_num(&self) -> u64 {
+        match self {
+            Cell::Susceptible => 0,
+            Cell::Exposed(_) => 5,
+            Cell::Infected(_) => 2,
+            Cell::Recovered => 3,
+            Cell::Vaccinated => 4,
+        }
+    }
+}
+
+// Parameters of the epidemic model.
+// Every step each infected neighbor of a susceptible cell infects it with the contact probability.
+// Infected cells are exposed for `incubation` steps, then infectious for `recovery` steps and
+// recovered (immune) afterwards. With an incubation of 0 the model is SIR instead of SEIR.
+pub struct EpidemicParams {
+    // per-contact infection probability per step
+    pub beta: f64,
+    pub incubation: u32,
+    pub recovery: u32,
+    pub neighborhood: Neighborhood,
+    // fraction of cells vaccinated (immune) and number of infected cells at the start,
+    // used when the automaton is restarted
+    pub vaccinated: f64,
+    pub initial_infected: usize,
+}
+
+impl Default for EpidemicParams {
+    fn default() -> EpidemicParams {
+        EpidemicParams {
+            beta: 0.2,
+            incubation: 3,
+            recovery: 7,
+            neighborhood: Neighborhood::Moore(1),
+            vaccinated: 0.0,
+            initial_infected: 5,
+        }
+    }
+}
+
+// Number of cells in every state at one step.
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    susceptible: usize,
+    exposed: usize,
+    infected: usize,
+    recovered: usize,
+    vaccinated: usize,
+}
+
+impl Counts {
+    fn of(x: &Vec<Vec<Cell>>) -> Counts {
+        let mut counts = Counts::default();
+        for cell in x.iter().flatten() {
+            match cell {
+                Cell::Susceptible => counts.susceptible += 1,
+                Cell::Exposed(_) => counts.exposed += 1,
+                Cell::Infected(_) => counts.infected += 1,
+                Cell::Recovered => counts.recovered += 1,
+                Cell::Vaccinated => counts.vaccinated += 1,
+            }
+        }
+        return counts;
+    }
+}
+
+// Global state of the epidemic model on a torus.
+// The population of every state is recorded after every step and, if an output file is given,
+// written to it as CSV. The file is rewritten when the automaton is restarted.
+pub struct EpidemicState {
+    params: EpidemicParams,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    output_path: Option<String>,
+    output: Option<File>,
+    // steps since the start and the populations after the last one
+    step: usize,
+    last: Counts,
+    peak_infected: usize,
+    peak_step: usize,
+}
+
+impl GlobalState for EpidemicState {
+    fn description(&self) -> String {
+        if self.params.incubation == 0 { "SIR epidemic".to_string() } else { "SEIR epidemic".to_string() }
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let (kind, radius) = match self.params.neighborhood {
+            Neighborhood::Moore(k) => (0, k),
+            Neighborhood::VonNeumann(k) => (1, k),
+        };
+        vec![
+            Param::float("Infection probability", self.params.beta, 0.0, 1.0),
+            Param::int("Incubation steps", self.params.incubation as i64, 0, 30),
+            Param::int("Recovery steps", self.params.recovery as i64, 1, 50),
+            Param::choice("Neighborhood", kind, &NEIGHBORHOOD_OPTIONS),
+            Param::int("Radius", radius as i64, 1, 3),
+            Param::float("Vaccinated", self.params.vaccinated, 0.0, 1.0),
+            Param::int("Initially infected", self.params.initial_infected as i64, 1, 100),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Infection probability" => self.params.beta = value,
+            "Incubation steps" => self.params.incubation = value as u32,
+            "Recovery steps" => self.params.recovery = value as u32,
+            "Neighborhood" => {
+                let radius = match self.params.neighborhood {
+                    Neighborhood::Moore(k) | Neighborhood::VonNeumann(k) => k,
+                };
+                self.params.neighborhood = if value as usize == 1 { Neighborhood::VonNeumann(radius) } else { Neighborhood::Moore(radius) };
+            },
+            "Radius" => self.params.neighborhood = self.params.neighborhood.with_radius(value as usize),
+            "Vaccinated" => self.params.vaccinated = value,
+            "Initially infected" => self.params.initial_infected = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let c = self.last;
+        let total = (c.susceptible + c.exposed + c.infected + c.recovered + c.vaccinated).max(1) as f64;
+        vec![
+            ("Susceptible".to_string(), c.susceptible as f64 / total),
+            ("Exposed".to_string(), c.exposed as f64 / total),
+            ("Infected".to_string(), c.infected as f64 / total),
+            ("Recovered".to_string(), c.recovered as f64 / total),
+            ("Vaccinated".to_string(), c.vaccinated as f64 / total),
+            ("Peak infected".to_string(), self.peak_infected as f64 / total),
+            ("Peak step".to_string(), self.peak_step as f64),
+        ]
+    }
+}
+
+impl EpidemicState {
+    fn record(&mut self, x: &Vec<Vec<Cell>>) {
+        let counts = Counts::of(x);
+        let step = self.step;
+        self.step += 1;
+        if counts.infected > self.peak_infected {
+            self.peak_infected = counts.infected;
+            self.peak_step = step;
+        }
+        self.last = counts;
+
+        let mut failed = false;
+        if let Some(file) = self.output.as_mut() {
+            let c = counts;
+            failed = writeln!(file, "{},{},{},{},{},{}", step, c.susceptible, c.exposed, c.infected, c.recovered, c.vaccinated).is_err();
+        }
+        if failed {
+            println!("could not write to the output file, stopping the export");
+            self.output = None;
+        }
+    }
+
+    // Creates the output file and writes the header, if an output file was given.
+    fn open_output(&mut self) {
+        self.output = None;
+        if let Some(path) = &self.output_path {
+            let file = File::create(path).and_then(|mut f| {
+                writeln!(f, "step,susceptible,exposed,infected,recovered,vaccinated")?;
+                Ok(f)
+            });
+            match file {
+                Ok(f) => self.output = Some(f),
+                Err(e) => println!("could not create output file '{}': {}", path, e),
+            }
+        }
+    }
+
+    // A newly infected cell, it skips the exposed state if there is no incubation.
+    fn infection(&self) -> Cell {
+        if self.params.incubation == 0 {
+            Cell::Infected(self.params.recovery)
+        } else {
+            Cell::Exposed(self.params.incubation)
+        }
+    }
+}
+
+// Creates the epidemic model on an n by m torus.
+// If an output path is given, the population of every state is written to it after every step.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_epidemic_automaton(n: usize, m: usize, params: EpidemicParams, output: Option<String>, seed: Option<u64>) -> Automaton<Cell, EpidemicState> {
+    let state = EpidemicState {
+        params: EpidemicParams { recovery: params.recovery.max(1), ..params },
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        output_path: output,
+        output: None,
+        step: 0,
+        last: Counts::default(),
+        peak_infected: 0,
+        peak_step: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+fn init_fn(n: usize, m: usize, state: &mut EpidemicState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![Cell::Susceptible; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            if state.rng.chance(state.params.vaccinated) {
+                *cell = Cell::Vaccinated;
+            }
+        }
+    }
+    // infect random susceptible cells, giving up if there are (almost) none
+    let mut infected = 0;
+    for _ in 0..100 * state.params.initial_infected {
+        if infected == state.params.initial_infected {
+            break;
+        }
+        let (i, j) = (state.rng.sample(0, n), state.rng.sample(0, m));
+        if grid[i][j] == Cell::Susceptible {
+            grid[i][j] = Cell::Infected(state.params.recovery);
+            infected += 1;
+        }
+    }
+
+    state.step = 0;
+    state.peak_infected = 0;
+    state.peak_step = 0;
+    state.open_output();
+    state.record(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut EpidemicState) {
+    let n = x.len();
+    let m = x[0].len();
+    let offsets = y.params.neighborhood.offsets();
+
+    let mut next = x.clone();
+    for i in 0..n {
+        for j in 0..m {
+            next[i][j] = match x[i][j] {
+                Cell::Susceptible => {
+                    let contacts = offsets.iter()
+                        .filter(|(di, dj)| {
+                            let (ni, nj) = wrap(i, j, *di, *dj, n, m);
+                            matches!(x[ni][nj], Cell::Infected(_))
+                        })
+                        .count();
+                    // every contact transmits independently
+                    let p = 1.0 - (1.0 - y.params.beta).powi(contacts as i32);
+                    if contacts > 0 && y.rng.chance(p) { y.infection() } else { Cell::Susceptible }
+                },
+                Cell::Exposed(t) if t > 1 => Cell::Exposed(t - 1),
+                Cell::Exposed(_) => Cell::Infected(y.params.recovery),
+                Cell::Infected(t) if t > 1 => Cell::Infected(t - 1),
+                Cell::Infected(_) => Cell::Recovered,
+                cell => cell,
+            };
+        }
+    }
+    *x = next;
+    y.record(x);
+}
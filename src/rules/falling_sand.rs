@@ -0,0 +1,303 @@
+use crate::automaton::{Automaton, GlobalState, Param, ScanOrder, UpdateMarks};
+use crate::image::{FlatImg, interpolate};
+use crate::rng;
+
+// Lifetimes of fire and smoke in steps, new particles get a random lifetime up to these values.
+const FIRE_LIFE: u8 = 40;
+const SMOKE_LIFE: u8 = 60;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Sand,
+    Water,
+    Stone,
+    // fire and smoke with their remaining lifetime
+    Fire(u8),
+    Smoke(u8),
+}
+
+const BRUSHES: [&str; 6] = ["Sand", "Water", "Stone", "Fire", "Smoke", "Eraser"];
+
+impl Cell {
+    // Returns true if a falling particle of this material can swap places with the other particle.
+    fn sinks_into(&self, other: Cell) -> bool {
+        match self {
+            Cell::Sand => matches!(other, Cell::Empty | Cell::Water | Cell::Smoke(_)),
+            Cell::Water => matches!(other, Cell::Empty | Cell::Smoke(_)),
+            _ => false,
+        }
+    }
+}
+
+// Global state of the falling sand simulation on a grid with solid borders.
+// Cells are updated in place from the bottom to the top, with the direction of the columns
+// alternating every step. A particle moves at most once per step and chooses randomly between
+// equally good moves, so no direction is preferred:
+// * sand falls down, or diagonally down, through empty cells, water and smoke
+// * water falls like sand and otherwise flows sideways
+// * stone never moves
+// * fire flickers upwards, is put out by water (turning some of it into steam) and burns out into
+//   smoke
+// * smoke rises, drifts sideways and dissipates
+pub struct FallingSandState {
+    // radius of the brush
+    brush_size: usize,
+    // if set, the grid starts with a scene of stone, sand and water instead of being empty
+    scene: bool,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    marks: UpdateMarks,
+    step: u64,
+    // number of cells of sand, water, stone, fire and smoke
+    counts: [usize; 5],
+}
+
+impl GlobalState for FallingSandState {
+    fn description(&self) -> String {
+        "Falling sand".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("Brush size", self.brush_size as i64, 0, 10),
+            Param::choice("Start", self.scene as usize, &["empty", "scene"]),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Brush size" => self.brush_size = value as usize,
+            // used when the automaton is restarted
+            "Start" => self.scene = value as usize == 1,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        BRUSHES.iter().zip(self.counts.iter())
+            .map(|(name, count)| (name.to_string(), *count as f64))
+            .collect()
+    }
+
+    fn brushes(&self) -> Vec<String> {
+        BRUSHES.iter().map(|b| b.to_string()).collect()
+    }
+}
+
+impl FallingSandState {
+    fn count(&mut self, x: &Vec<Vec<Cell>>) {
+        self.counts = [0; 5];
+        for cell in x.iter().flatten() {
+            let index = match cell {
+                Cell::Sand => 0,
+                Cell::Water => 1,
+                Cell::Stone => 2,
+                Cell::Fire(_) => 3,
+                Cell::Smoke(_) => 4,
+                Cell::Empty => continue,
+            };
+            self.counts[index] += 1;
+        }
+    }
+
+    fn fire(&mut self) -> Cell {
+        Cell::Fire(self.rng.sample(FIRE_LIFE / 2, FIRE_LIFE + 1))
+    }
+
+    fn smoke(&mut self) -> Cell {
+        Cell::Smoke(self.rng.sample(SMOKE_LIFE / 2, SMOKE_LIFE + 1))
+    }
+
+    // Returns -1 or 1 with equal probability.
+    fn random_side(&mut self) -> isize {
+        if self.rng.chance(0.5) { -1 } else { 1 }
+    }
+}
+
+// Creates the falling sand simulation on an n by m grid.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_falling_sand_automaton(n: usize, m: usize, scene: bool, seed: Option<u64>) -> Automaton<Cell, FallingSandState> {
+    let state = FallingSandState {
+        brush_size: 3,
+        scene: scene,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        marks: UpdateMarks::new(0, 0),
+        step: 0,
+        counts: [0; 5],
+    };
+    Automaton::new(n, m, state, init_fn, next_fn).with_paint_fn(paint_fn)
+}
+
+pub fn transform(grid: &Vec<Vec<Cell>>, _state: &FallingSandState) -> FlatImg {
+    FlatImg::from_2d_vec_with(grid, |cell| match cell {
+        Cell::Empty => [255, 255, 255],
+        Cell::Sand => [210, 180, 110],
+        Cell::Water => [30, 100, 220],
+        Cell::Stone => [100, 100, 100],
+        Cell::Fire(life) => interpolate([255, 40, 0], [255, 220, 0], *life as f32 / FIRE_LIFE as f32),
+        Cell::Smoke(life) => interpolate([255, 255, 255], [120, 120, 120], *life as f32 / SMOKE_LIFE as f32),
+    })
+}
+
+fn init_fn(n: usize, m: usize, state: &mut FallingSandState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    state.marks = UpdateMarks::new(n, m);
+    state.step = 0;
+    let mut grid = vec![vec![Cell::Empty; m]; n];
+    if state.scene {
+        // a stone basin in the lower half, with a block of sand above its left and of water above
+        // its right side
+        let floor = n * 3 / 4;
+        for j in m / 4..m * 3 / 4 {
+            grid[floor][j] = Cell::Stone;
+        }
+        for i in n / 2..floor {
+            grid[i][m / 4] = Cell::Stone;
+            grid[i][m * 3 / 4] = Cell::Stone;
+        }
+        for i in n / 10..n / 4 {
+            for j in m / 5..m * 2 / 5 {
+                grid[i][j] = Cell::Sand;
+            }
+            for j in m * 3 / 5..m * 4 / 5 {
+                grid[i][j] = Cell::Water;
+            }
+        }
+    }
+    state.count(&grid);
+    return grid;
+}
+
+// Fills the disk of the brush around (i, j). Stone and the eraser replace everything, the other
+// materials only fill empty cells.
+fn paint_fn(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState, i: usize, j: usize, brush: usize) {
+    let n = x.len() as isize;
+    let m = x[0].len() as isize;
+    let r = y.brush_size as isize;
+    for di in -r..=r {
+        for dj in -r..=r {
+            let (ci, cj) = (i as isize + di, j as isize + dj);
+            if di * di + dj * dj > r * r || ci < 0 || cj < 0 || ci >= n || cj >= m {
+                continue;
+            }
+            let (ci, cj) = (ci as usize, cj as usize);
+            let cell = match brush {
+                0 => Cell::Sand,
+                1 => Cell::Water,
+                2 => Cell::Stone,
+                3 => y.fire(),
+                4 => y.smoke(),
+                _ => Cell::Empty,
+            };
+            if matches!(cell, Cell::Stone | Cell::Empty) || x[ci][cj] == Cell::Empty {
+                x[ci][cj] = cell;
+            }
+        }
+    }
+    y.count(x);
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState) {
+    let n = x.len();
+    let m = x[0].len();
+    y.marks.next_step();
+    for (i, j) in ScanOrder::alternating(true, y.step).cells(n, m) {
+        if y.marks.is_marked(i, j) {
+            continue;
+        }
+        match x[i][j] {
+            Cell::Sand => fall(x, y, i, j, false),
+            Cell::Water => fall(x, y, i, j, true),
+            Cell::Fire(life) => burn(x, y, i, j, life),
+            Cell::Smoke(life) => {
+                if life <= 1 {
+                    x[i][j] = Cell::Empty;
+                } else {
+                    x[i][j] = Cell::Smoke(life - 1);
+                    let side = y.random_side();
+                    rise(x, y, i, j, &[(-1, 0), (-1, side), (-1, -side), (0, side)]);
+                }
+            },
+            Cell::Stone | Cell::Empty => {},
+        }
+    }
+    y.step += 1;
+    y.count(x);
+}
+
+// Returns the cell at offset (di, dj) from (i, j), or None if it is outside the grid.
+fn offset(x: &Vec<Vec<Cell>>, i: usize, j: usize, di: isize, dj: isize) -> Option<(usize, usize)> {
+    let (ni, nj) = (i as isize + di, j as isize + dj);
+    if ni < 0 || nj < 0 || ni >= x.len() as isize || nj >= x[0].len() as isize {
+        return None;
+    }
+    return Some((ni as usize, nj as usize));
+}
+
+// Swaps the particles at (i, j) and (ni, nj) and marks both as updated.
+fn swap(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState, i: usize, j: usize, ni: usize, nj: usize) {
+    let cell = x[i][j];
+    x[i][j] = x[ni][nj];
+    x[ni][nj] = cell;
+    y.marks.mark(i, j);
+    y.marks.mark(ni, nj);
+}
+
+// Moves sand or water down, diagonally down or, for liquids, sideways.
+fn fall(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState, i: usize, j: usize, liquid: bool) {
+    let cell = x[i][j];
+    let side = y.random_side();
+    let mut moves = vec![(1, 0), (1, side), (1, -side)];
+    if liquid {
+        moves.extend([(0, side), (0, -side)]);
+    }
+    for (di, dj) in moves {
+        if let Some((ni, nj)) = offset(x, i, j, di, dj) {
+            // diagonal moves must not pass through a corner between two blocked cells
+            let open = dj == 0 || di == 0 || cell.sinks_into(x[i][nj]);
+            if open && cell.sinks_into(x[ni][nj]) {
+                swap(x, y, i, j, ni, nj);
+                return;
+            }
+        }
+    }
+}
+
+// Moves fire or smoke to the first of the given offsets that is empty.
+fn rise(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState, i: usize, j: usize, moves: &[(isize, isize)]) {
+    for (di, dj) in moves {
+        if let Some((ni, nj)) = offset(x, i, j, *di, *dj) {
+            if x[ni][nj] == Cell::Empty {
+                swap(x, y, i, j, ni, nj);
+                return;
+            }
+        }
+    }
+}
+
+fn burn(x: &mut Vec<Vec<Cell>>, y: &mut FallingSandState, i: usize, j: usize, life: u8) {
+    // water puts out the fire, some of it evaporates
+    let mut extinguished = false;
+    for (di, dj) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+        if let Some((ni, nj)) = offset(x, i, j, di, dj) {
+            if x[ni][nj] == Cell::Water {
+                extinguished = true;
+                if y.rng.chance(0.3) {
+                    x[ni][nj] = y.smoke();
+                    y.marks.mark(ni, nj);
+                }
+            }
+        }
+    }
+    if extinguished || life <= 1 {
+        x[i][j] = if extinguished || y.rng.chance(0.5) { y.smoke() } else { Cell::Empty };
+        return;
+    }
+    x[i][j] = Cell::Fire(life - 1);
+    if y.rng.chance(0.5) {
+        let side = y.random_side();
+        rise(x, y, i, j, &[(-1, 0), (-1, side), (-1, -side)]);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param, wrap};
+use crate::image::ToNum;
+use crate::rng;
+use crate::statistics::LogHistogram;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Tree,
+    // a burning tree, belonging to the fire with the given id
+    Burning(u64),
+}
+
+impl ToNum for Cell {
+    // white, green and red in the palette of num_to_rgb
+    fn to_num(&self) -> u64 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Tree => 3,
+            Cell::Burning(_) => 2,
+        }
+    }
+}
+
+// Global state of the Drossel-Schwabl forest-fire model on a torus.
+// Every step burning trees burn down, trees next to a burning tree catch fire, trees are struck
+// by lightning with probability f and new trees grow on empty cells with probability p.
+//
+// Every fire started by lightning gets an id, so its size (the number of trees it burned) can be
+// recorded once it has burned out. When two fires meet, trees they ignite together are attributed
+// to only one of them.
+pub struct ForestFireState {
+    p: f64,
+    f: f64,
+    // fraction of cells with trees at the start
+    initial_density: f64,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    next_fire: u64,
+    // number of trees burned so far by every fire that is still burning
+    active_fires: HashMap<u64, usize>,
+    // sizes of the burned out fires
+    fire_sizes: LogHistogram,
+    clusters: ClusterStats,
+}
+
+// Statistics of the clusters of trees connected through their von Neumann neighbors.
+#[derive(Default)]
+struct ClusterStats {
+    trees: usize,
+    count: usize,
+    largest: usize,
+}
+
+impl GlobalState for ForestFireState {
+    fn description(&self) -> String {
+        "Drossel-Schwabl forest fire".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::float("Growth probability p", self.p, 0.0, 0.1),
+            Param::float("Lightning probability f", self.f, 0.0, 0.001),
+            Param::float("Initial density", self.initial_density, 0.0, 1.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Growth probability p" => self.p = value,
+            "Lightning probability f" => self.f = value,
+            // used when the automaton is restarted
+            "Initial density" => self.initial_density = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let c = &self.clusters;
+        let mean_cluster = if c.count > 0 { c.trees as f64 / c.count as f64 } else { 0.0 };
+        let mut stats = vec![
+            ("Trees".to_string(), c.trees as f64),
+            ("Tree clusters".to_string(), c.count as f64),
+            ("Mean cluster size".to_string(), mean_cluster),
+            ("Largest cluster".to_string(), c.largest as f64),
+            ("Burning fires".to_string(), self.active_fires.len() as f64),
+            ("Burned out fires".to_string(), self.fire_sizes.count() as f64),
+            ("Mean fire size".to_string(), self.fire_sizes.mean()),
+            ("Largest fire".to_string(), self.fire_sizes.max() as f64),
+        ];
+        stats.extend(self.fire_sizes.stats("Fires of size"));
+        stats
+    }
+}
+
+// Creates the forest-fire model on an n by m torus.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_forest_fire_automaton(n: usize, m: usize, p: f64, f: f64, initial_density: f64, seed: Option<u64>) -> Automaton<Cell, ForestFireState> {
+    let state = ForestFireState {
+        p: p,
+        f: f,
+        initial_density: initial_density,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        next_fire: 0,
+        active_fires: HashMap::new(),
+        fire_sizes: LogHistogram::new(),
+        clusters: ClusterStats::default(),
+    };
+    Automaton::new(n, m, state, init_random, next_fn)
+}
+
+fn init_random(n: usize, m: usize, state: &mut ForestFireState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![Cell::Empty; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            if state.rng.chance(state.initial_density) {
+                *cell = Cell::Tree;
+            }
+        }
+    }
+    state.next_fire = 0;
+    state.active_fires.clear();
+    state.fire_sizes = LogHistogram::new();
+    state.clusters = cluster_stats(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut ForestFireState) {
+    let n = x.len();
+    let m = x[0].len();
+    let offsets = Neighborhood::VonNeumann(1).offsets();
+
+    let mut next = x.clone();
+    // number of trees every fire ignited in this step
+    let mut ignited: HashMap<u64, usize> = HashMap::new();
+    for i in 0..n {
+        for j in 0..m {
+            next[i][j] = match x[i][j] {
+                Cell::Burning(_) => Cell::Empty,
+                Cell::Empty => if y.rng.chance(y.p) { Cell::Tree } else { Cell::Empty },
+                Cell::Tree => {
+                    let fire = offsets.iter().find_map(|(di, dj)| {
+                        let (ni, nj) = wrap(i, j, *di, *dj, n, m);
+                        match x[ni][nj] {
+                            Cell::Burning(id) => Some(id),
+                            _ => None,
+                        }
+                    });
+                    match fire {
+                        Some(id) => Cell::Burning(id),
+                        None if y.rng.chance(y.f) => {
+                            y.next_fire += 1;
+                            Cell::Burning(y.next_fire)
+                        },
+                        None => Cell::Tree,
+                    }
+                },
+            };
+            if let Cell::Burning(id) = next[i][j] {
+                *ignited.entry(id).or_insert(0) += 1;
+            }
+        }
+    }
+    *x = next;
+
+    // fires that did not ignite any trees have burned out
+    let previous = std::mem::take(&mut y.active_fires);
+    for (id, size) in previous {
+        match ignited.remove(&id) {
+            Some(count) => { y.active_fires.insert(id, size + count); },
+            None => y.fire_sizes.add(size),
+        }
+    }
+    // fires started by lightning in this step
+    y.active_fires.extend(ignited);
+    y.clusters = cluster_stats(x);
+}
+
+// Finds the clusters of trees with a flood fill over von Neumann neighbors on the torus.
+fn cluster_stats(x: &Vec<Vec<Cell>>) -> ClusterStats {
+    let n = x.len();
+    let m = x[0].len();
+    let offsets = Neighborhood::VonNeumann(1).offsets();
+    let mut visited = vec![vec![false; m]; n];
+    let mut stats = ClusterStats::default();
+    let mut stack = Vec::new();
+    for i in 0..n {
+        for j in 0..m {
+            if visited[i][j] || x[i][j] != Cell::Tree {
+                continue;
+            }
+            let mut size = 0;
+            visited[i][j] = true;
+            stack.push((i, j));
+            while let Some((ci, cj)) = stack.pop() {
+                size += 1;
+                for (di, dj) in &offsets {
+                    let (ni, nj) = wrap(ci, cj, *di, *dj, n, m);
+                    if !visited[ni][nj] && x[ni][nj] == Cell::Tree {
+                        visited[ni][nj] = true;
+                        stack.push((ni, nj));
+                    }
+                }
+            }
+            stats.trees += size;
+            stats.count += 1;
+            stats.largest = stats.largest.max(size);
+        }
+    }
+    return stats;
+}
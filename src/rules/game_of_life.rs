@@ -2,10 +2,10 @@ use crate::automaton::{Automaton, neighbors};
 use crate::rng;
 
 pub fn new_gol_automaton(n: usize, m: usize) -> Automaton<u8, ()> {
-    Automaton::new(n, m, init_random, rule_next_fn)
+    Automaton::new(n, m, (), init_random, rule_next_fn)
 }
 
-fn init_random(n: usize, m: usize) -> (Vec<Vec<u8>>, ()) {
+fn init_random(n: usize, m: usize, _: &mut ()) -> Vec<Vec<u8>> {
     let mut initial_grid = vec![vec![0; m]; n];
     let mut rng = rng::UniformRng::new();
 	for i in 1..n-1 {
@@ -15,7 +15,7 @@ fn init_random(n: usize, m: usize) -> (Vec<Vec<u8>>, ()) {
         	}
 		}
     }
-    return (initial_grid, ());
+    return initial_grid;
 }
 
 fn rule_next_fn(x: &mut Vec<Vec<u8>>, _: &mut ()) {
@@ -27,7 +27,7 @@ fn rule_next_fn(x: &mut Vec<Vec<u8>>, _: &mut ()) {
 	for i in 0..n {
     	for j in 0..m {
 			let mut live_neighbours = 0;
-			for nb in neighbors(i, j, &x, 1) {
+			for nb in neighbors(i, j, x, 1) {
 				if *nb == 1 {
 					live_neighbours += 1;
 				}
@@ -0,0 +1,356 @@
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::image::{FlatImg, colormap};
+use crate::rng;
+
+// Cells hold 0 if they are empty and k if they hold the k-th particle attached to the cluster.
+
+// A cluster growing from a single particle, with the statistics of its shape.
+struct Cluster {
+    // position of the first particle
+    center: (usize, usize),
+    particles: usize,
+    // largest distance of a particle from the center
+    radius: f64,
+    // sum of the squared distances from the center
+    squared_distances: f64,
+    // number of particles at each integer distance from the center
+    mass_by_radius: Vec<usize>,
+}
+
+impl Cluster {
+    // Starts an empty cluster around the middle of an n by m grid.
+    fn new(n: usize, m: usize) -> Cluster {
+        Cluster {
+            center: (n / 2, m / 2),
+            particles: 0,
+            radius: 0.0,
+            squared_distances: 0.0,
+            mass_by_radius: vec![],
+        }
+    }
+
+    // Starts the cluster over on the grid with one particle at the center.
+    fn reset(&mut self, grid: &mut Vec<Vec<u32>>) {
+        *self = Cluster::new(grid.len(), grid[0].len());
+        self.add(grid, self.center.0, self.center.1);
+    }
+
+    fn distance(&self, i: usize, j: usize) -> f64 {
+        let (di, dj) = (i as f64 - self.center.0 as f64, j as f64 - self.center.1 as f64);
+        di.hypot(dj)
+    }
+
+    fn add(&mut self, grid: &mut Vec<Vec<u32>>, i: usize, j: usize) {
+        self.particles += 1;
+        grid[i][j] = self.particles as u32;
+        let d = self.distance(i, j);
+        self.radius = self.radius.max(d);
+        self.squared_distances += d * d;
+        let bin = d as usize;
+        if bin >= self.mass_by_radius.len() {
+            self.mass_by_radius.resize(bin + 1, 0);
+        }
+        self.mass_by_radius[bin] += 1;
+    }
+
+    // Estimates the fractal dimension as the slope of log N(r) against log r, where N(r) is the
+    // number of particles closer than r to the center, at integer radii growing by a quarter.
+    // Radii close to the edge of the cluster, where it is still growing, are left out. Returns 0
+    // while the cluster is too small.
+    fn fractal_dimension(&self) -> f64 {
+        let mut points = vec![];
+        let mut r = 3;
+        while r as f64 <= 0.75 * self.radius {
+            let count: usize = self.mass_by_radius.iter().take(r).sum();
+            points.push((f64::ln(r as f64), f64::ln(count as f64)));
+            r = (r as f64 * 1.25).ceil() as usize;
+        }
+        if points.len() < 3 {
+            return 0.0;
+        }
+        let k = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / k;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / k;
+        let covariance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+        let variance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.0 - mean_x)).sum();
+        return covariance / variance;
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Particles".to_string(), self.particles as f64),
+            ("Radius".to_string(), self.radius),
+            ("Radius of gyration".to_string(), (self.squared_distances / self.particles.max(1) as f64).sqrt()),
+            ("Fractal dimension".to_string(), self.fractal_dimension()),
+        ]
+    }
+}
+
+// Shows the particles by the time they attached, from dark purple for the oldest to yellow for
+// the newest, on a white background.
+pub fn transform<S>(grid: &Vec<Vec<u32>>, _state: &S) -> FlatImg {
+    let newest = grid.iter().flatten().max().copied().unwrap_or(0).max(1) as f32;
+    FlatImg::from_2d_vec_with(grid, |k| if *k == 0 { [255, 255, 255] } else { colormap(*k as f32 / newest) })
+}
+
+// Moves a walker may make before it is given up, so that a low stickiness cannot keep a step
+// from finishing.
+const MAX_MOVES: usize = 1_000_000;
+
+const NEIGHBORS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// Returns the neighbor of (i, j) at the given offset, or None if it is outside the grid.
+fn neighbor(grid: &Vec<Vec<u32>>, i: usize, j: usize, (di, dj): (isize, isize)) -> Option<(usize, usize)> {
+    let (ni, nj) = (i as isize + di, j as isize + dj);
+    if ni < 0 || nj < 0 || ni >= grid.len() as isize || nj >= grid[0].len() as isize {
+        return None;
+    }
+    return Some((ni as usize, nj as usize));
+}
+
+// Global state of diffusion-limited aggregation (Witten and Sander). Walkers are released one
+// at a time on a circle around the cluster and move randomly between von Neumann neighbors
+// until they touch the cluster, where they stick with the given probability. Walkers that
+// wander too far away are released again, walkers that do not stick within a fixed number of
+// moves are given up. Growth stops when the cluster gets close to the edge
+// of the grid.
+pub struct DlaState {
+    // probability that a walker next to the cluster sticks to it
+    stickiness: f64,
+    particles_per_step: usize,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    cluster: Cluster,
+}
+
+impl GlobalState for DlaState {
+    fn description(&self) -> String {
+        "Diffusion-limited aggregation".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::float("Stickiness", self.stickiness, 0.01, 1.0),
+            Param::int("Particles per step", self.particles_per_step as i64, 1, 200),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Stickiness" => self.stickiness = value,
+            "Particles per step" => self.particles_per_step = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        self.cluster.stats()
+    }
+}
+
+// Creates diffusion-limited aggregation on an n by m grid, starting from a single particle in
+// the middle. The stickiness has to be in (0, 1]. If no seed is given, the random number
+// generator is seeded from entropy.
+pub fn new_dla_automaton(n: usize, m: usize, stickiness: f64, particles_per_step: usize, seed: Option<u64>) -> Result<Automaton<u32, DlaState>, String> {
+    if !(stickiness > 0.0 && stickiness <= 1.0) {
+        return Err("the stickiness must be greater than 0 and at most 1".to_string());
+    }
+    let state = DlaState {
+        stickiness: stickiness,
+        particles_per_step: particles_per_step,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        cluster: Cluster::new(n, m),
+    };
+    Ok(Automaton::new(n, m, state, init_dla, dla_next_fn))
+}
+
+fn init_dla(n: usize, m: usize, state: &mut DlaState) -> Vec<Vec<u32>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    state.cluster.reset(&mut grid);
+    return grid;
+}
+
+fn dla_next_fn(x: &mut Vec<Vec<u32>>, y: &mut DlaState) {
+    let n = x.len();
+    let m = x[0].len();
+    let cluster = &mut y.cluster;
+    let (ci, cj) = cluster.center;
+    // distance from the center to the closest edge of the grid
+    let room = ci.min(cj).min(n - 1 - ci).min(m - 1 - cj) as f64;
+
+    for _ in 0..y.particles_per_step {
+        let launch = cluster.radius + 5.0;
+        if launch >= room {
+            return;
+        }
+        let kill = (2.0 * launch + 10.0).min(room);
+        let angle = y.rng.sample(0.0, 2.0 * std::f64::consts::PI);
+        let mut i = (ci as f64 + launch * angle.sin()).round() as usize;
+        let mut j = (cj as f64 + launch * angle.cos()).round() as usize;
+        for _ in 0..MAX_MOVES {
+            let touching = NEIGHBORS.iter().any(|d| neighbor(x, i, j, *d).is_some_and(|(ni, nj)| x[ni][nj] != 0));
+            if touching && y.rng.chance(y.stickiness) {
+                cluster.add(x, i, j);
+                break;
+            }
+            let step = NEIGHBORS[y.rng.sample(0, NEIGHBORS.len())];
+            match neighbor(x, i, j, step) {
+                Some((ni, nj)) if x[ni][nj] == 0 => (i, j) = (ni, nj),
+                _ => continue,
+            }
+            if cluster.distance(i, j) > kill {
+                let angle = y.rng.sample(0.0, 2.0 * std::f64::consts::PI);
+                i = (ci as f64 + launch * angle.sin()).round() as usize;
+                j = (cj as f64 + launch * angle.cos()).round() as usize;
+            }
+        }
+    }
+}
+
+// Global state of the Eden growth model: every new particle is attached to a site chosen
+// uniformly at random among the empty von Neumann neighbors of the cluster. The cluster is
+// compact with a rough surface.
+pub struct EdenState {
+    particles_per_step: usize,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    cluster: Cluster,
+    // empty sites next to the cluster, with a flag for every cell whether it is in the list
+    perimeter: Vec<(usize, usize)>,
+    on_perimeter: Vec<Vec<bool>>,
+}
+
+impl GlobalState for EdenState {
+    fn description(&self) -> String {
+        "Eden growth".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("Particles per step", self.particles_per_step as i64, 1, 1000),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Particles per step" => self.particles_per_step = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        self.cluster.stats()
+    }
+}
+
+impl EdenState {
+    // Adds the empty neighbors of (i, j) to the perimeter.
+    fn extend_perimeter(&mut self, x: &Vec<Vec<u32>>, i: usize, j: usize) {
+        for d in NEIGHBORS {
+            if let Some((ni, nj)) = neighbor(x, i, j, d) {
+                if x[ni][nj] == 0 && !self.on_perimeter[ni][nj] {
+                    self.on_perimeter[ni][nj] = true;
+                    self.perimeter.push((ni, nj));
+                }
+            }
+        }
+    }
+}
+
+// Creates the Eden growth model on an n by m grid, starting from a single particle in the
+// middle. If no seed is given, the random number generator is seeded from entropy.
+pub fn new_eden_automaton(n: usize, m: usize, particles_per_step: usize, seed: Option<u64>) -> Automaton<u32, EdenState> {
+    let state = EdenState {
+        particles_per_step: particles_per_step,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        cluster: Cluster::new(n, m),
+        perimeter: vec![],
+        on_perimeter: vec![],
+    };
+    Automaton::new(n, m, state, init_eden, eden_next_fn)
+}
+
+fn init_eden(n: usize, m: usize, state: &mut EdenState) -> Vec<Vec<u32>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    state.cluster.reset(&mut grid);
+    let (ci, cj) = state.cluster.center;
+    state.perimeter.clear();
+    state.on_perimeter = vec![vec![false; m]; n];
+    state.extend_perimeter(&grid, ci, cj);
+    return grid;
+}
+
+fn eden_next_fn(x: &mut Vec<Vec<u32>>, y: &mut EdenState) {
+    for _ in 0..y.particles_per_step {
+        if y.perimeter.is_empty() {
+            return;
+        }
+        let (i, j) = y.perimeter.swap_remove(y.rng.sample(0, y.perimeter.len()));
+        y.on_perimeter[i][j] = false;
+        y.cluster.add(x, i, j);
+        y.extend_perimeter(x, i, j);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dla_rejects_invalid_stickiness() {
+        for stickiness in [0.0, -0.5, 1.5, f64::NAN] {
+            assert!(new_dla_automaton(40, 40, stickiness, 2, Some(1)).is_err());
+        }
+        assert!(new_dla_automaton(40, 40, 1.0, 2, Some(1)).is_ok());
+    }
+
+    #[test]
+    fn dla_with_low_stickiness_finishes_steps() {
+        let mut aut = new_dla_automaton(40, 40, 1e-12, 2, Some(1)).unwrap();
+        aut.next();
+        assert_eq!(aut.state().0.iter().flatten().filter(|k| **k != 0).count(), 1);
+    }
+
+    #[test]
+    fn eden_perimeter_is_the_empty_border_of_the_cluster() {
+        let mut aut = new_eden_automaton(30, 40, 50, Some(1));
+        for _ in 0..5 {
+            aut.next();
+            let (x, y) = aut.state();
+            assert_eq!(y.cluster.particles, x.iter().flatten().filter(|k| **k != 0).count());
+            let mut listed = vec![vec![false; 40]; 30];
+            for &(i, j) in y.perimeter.iter() {
+                assert!(!listed[i][j], "({}, {}) is listed twice", i, j);
+                listed[i][j] = true;
+            }
+            for i in 0..30 {
+                for j in 0..40 {
+                    let border = x[i][j] == 0 && NEIGHBORS.iter().any(|d| neighbor(x, i, j, *d).is_some_and(|(ni, nj)| x[ni][nj] != 0));
+                    assert_eq!(listed[i][j], border, "({}, {})", i, j);
+                    assert_eq!(y.on_perimeter[i][j], border, "({}, {})", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filled_disk_has_dimension_two() {
+        let mut grid = vec![vec![0; 101]; 101];
+        let mut cluster = Cluster::new(101, 101);
+        cluster.reset(&mut grid);
+        for i in 0..101 {
+            for j in 0..101 {
+                if grid[i][j] == 0 && cluster.distance(i, j) <= 45.0 {
+                    cluster.add(&mut grid, i, j);
+                }
+            }
+        }
+        let dimension = cluster.fractal_dimension();
+        assert!((dimension - 2.0).abs() < 0.1, "dimension {}", dimension);
+    }
+}
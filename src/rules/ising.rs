@@ -0,0 +1,183 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::rng;
+
+// How spins are updated, every step of the automaton is one sweep over the lattice.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Dynamics {
+    // n*m random sites, a flip is accepted with probability min(1, exp(-dE / T))
+    Metropolis,
+    // n*m random sites, a flip is accepted with probability 1 / (1 + exp(dE / T))
+    Glauber,
+    // Metropolis updates of all sites of one color of the checkerboard, then of the other color
+    Checkerboard,
+}
+
+const DYNAMICS_OPTIONS: [&str; 3] = ["Metropolis", "Glauber", "checkerboard"];
+
+impl FromStr for Dynamics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dynamics, String> {
+        match s {
+            "metropolis" => Ok(Dynamics::Metropolis),
+            "glauber" => Ok(Dynamics::Glauber),
+            "checkerboard" => Ok(Dynamics::Checkerboard),
+            _ => Err(format!("unknown dynamics '{}', expected 'metropolis', 'glauber' or 'checkerboard'", s)),
+        }
+    }
+}
+
+// Global state of the two-dimensional Ising model on a torus with coupling J = 1.
+// Cells are 1 for spin up and 0 for spin down. The model has a phase transition at the critical
+// temperature 2 / ln(1 + sqrt(2)) = 2.269 without external field.
+pub struct IsingState {
+    temperature: f64,
+    field: f64,
+    dynamics: Dynamics,
+    // if set, all spins start up instead of random
+    ordered_start: bool,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // per spin
+    magnetization: f64,
+    energy: f64,
+}
+
+impl GlobalState for IsingState {
+    fn description(&self) -> String {
+        "Ising model".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let dynamics = match self.dynamics {
+            Dynamics::Metropolis => 0,
+            Dynamics::Glauber => 1,
+            Dynamics::Checkerboard => 2,
+        };
+        vec![
+            Param::float("Temperature", self.temperature, 0.1, 5.0),
+            Param::float("Field", self.field, -1.0, 1.0),
+            Param::choice("Dynamics", dynamics, &DYNAMICS_OPTIONS),
+            Param::choice("Start", self.ordered_start as usize, &["random", "all up"]),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Temperature" => self.temperature = value,
+            "Field" => self.field = value,
+            "Dynamics" => self.dynamics = match value as usize {
+                1 => Dynamics::Glauber,
+                2 => Dynamics::Checkerboard,
+                _ => Dynamics::Metropolis,
+            },
+            // used when the automaton is restarted
+            "Start" => self.ordered_start = value as usize == 1,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Magnetization".to_string(), self.magnetization),
+            ("|Magnetization|".to_string(), self.magnetization.abs()),
+            ("Energy".to_string(), self.energy),
+        ]
+    }
+}
+
+impl IsingState {
+    fn update_observables(&mut self, x: &Vec<Vec<u8>>) {
+        let n = x.len();
+        let m = x[0].len();
+        let mut magnetization = 0.0;
+        let mut energy = 0.0;
+        for i in 0..n {
+            for j in 0..m {
+                let s = spin(x[i][j]);
+                // count every bond once, to the right and down
+                energy -= s * (spin(x[i][(j + 1) % m]) + spin(x[(i + 1) % n][j]));
+                energy -= self.field * s;
+                magnetization += s;
+            }
+        }
+        let spins = (n * m) as f64;
+        self.magnetization = magnetization / spins;
+        self.energy = energy / spins;
+    }
+
+    // Flips the spin at (i, j) with the acceptance probability of the given dynamics.
+    fn try_flip(&mut self, x: &mut Vec<Vec<u8>>, i: usize, j: usize, dynamics: Dynamics) {
+        let n = x.len();
+        let m = x[0].len();
+        let s = spin(x[i][j]);
+        let neighbors = spin(x[(i + n - 1) % n][j]) + spin(x[(i + 1) % n][j])
+            + spin(x[i][(j + m - 1) % m]) + spin(x[i][(j + 1) % m]);
+        let delta = 2.0 * s * (neighbors + self.field);
+        let accept = match dynamics {
+            Dynamics::Glauber => 1.0 / (1.0 + (delta / self.temperature).exp()),
+            _ => if delta <= 0.0 { 1.0 } else { (-delta / self.temperature).exp() },
+        };
+        if self.rng.chance(accept) {
+            x[i][j] = 1 - x[i][j];
+        }
+    }
+}
+
+fn spin(v: u8) -> f64 {
+    if v == 1 { 1.0 } else { -1.0 }
+}
+
+// Creates the Ising model on an n by m torus.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_ising_automaton(n: usize, m: usize, temperature: f64, field: f64, dynamics: Dynamics, seed: Option<u64>) -> Automaton<u8, IsingState> {
+    let state = IsingState {
+        temperature: temperature,
+        field: field,
+        dynamics: dynamics,
+        ordered_start: false,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        magnetization: 0.0,
+        energy: 0.0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+fn init_fn(n: usize, m: usize, state: &mut IsingState) -> Vec<Vec<u8>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![1; m]; n];
+    if !state.ordered_start {
+        for row in grid.iter_mut() {
+            for s in row.iter_mut() {
+                *s = state.rng.sample(0, 2);
+            }
+        }
+    }
+    state.update_observables(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<u8>>, y: &mut IsingState) {
+    let n = x.len();
+    let m = x[0].len();
+    match y.dynamics {
+        Dynamics::Checkerboard => {
+            for color in 0..2 {
+                for i in 0..n {
+                    for j in ((i + color) % 2..m).step_by(2) {
+                        y.try_flip(x, i, j, Dynamics::Metropolis);
+                    }
+                }
+            }
+        },
+        dynamics => {
+            for _ in 0..n*m {
+                let (i, j) = (y.rng.sample(0, n), y.rng.sample(0, m));
+                y.try_flip(x, i, j, dynamics);
+            }
+        },
+    }
+    y.update_observables(x);
+}
@@ -0,0 +1,411 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::image::{FlatImg, colormap, interpolate};
+
+// Velocities of the D2Q9 lattice as (dx, dy) with y pointing up: at rest, east, north, west,
+// south, north-east, north-west, south-west and south-east.
+const VELOCITIES: [(isize, isize); 9] = [(0, 0), (1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, 1), (-1, -1), (1, -1)];
+const WEIGHTS: [f32; 9] = [4.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0];
+// index of the opposite velocity
+const OPPOSITE: [usize; 9] = [0, 3, 4, 1, 2, 7, 8, 5, 6];
+
+const BRUSHES: [&str; 2] = ["Obstacle", "Eraser"];
+
+// Particle distributions along the velocities of the lattice.
+#[derive(Clone, Copy)]
+pub struct Cell {
+    f: [f32; 9],
+    obstacle: bool,
+}
+
+impl Cell {
+    // Returns the density and the velocity of the fluid in the cell.
+    fn moments(&self) -> (f32, f32, f32) {
+        let (mut rho, mut ux, mut uy) = (0.0, 0.0, 0.0);
+        for k in 0..9 {
+            rho += self.f[k];
+            ux += self.f[k] * VELOCITIES[k].0 as f32;
+            uy += self.f[k] * VELOCITIES[k].1 as f32;
+        }
+        return (rho, ux / rho, uy / rho);
+    }
+}
+
+// Returns a cell in equilibrium with the given density and velocity.
+fn equilibrium(rho: f32, ux: f32, uy: f32) -> Cell {
+    let mut f = [0.0; 9];
+    let u2 = ux * ux + uy * uy;
+    for k in 0..9 {
+        let eu = VELOCITIES[k].0 as f32 * ux + VELOCITIES[k].1 as f32 * uy;
+        f[k] = WEIGHTS[k] * rho * (1.0 + 3.0 * eu + 4.5 * eu * eu - 1.5 * u2);
+    }
+    Cell { f: f, obstacle: false }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scene {
+    // a cylinder slightly off the center line, which sheds a vortex street
+    Cylinder,
+    // a tilted flat plate
+    Plate,
+    // no obstacles, they can be painted with the mouse
+    Empty,
+}
+
+const SCENES: [Scene; 3] = [Scene::Cylinder, Scene::Plate, Scene::Empty];
+
+impl FromStr for Scene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Scene, String> {
+        match s {
+            "cylinder" => Ok(Scene::Cylinder),
+            "plate" => Ok(Scene::Plate),
+            "empty" => Ok(Scene::Empty),
+            _ => Err(format!("unknown scene '{}', expected 'cylinder', 'plate' or 'empty'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum View {
+    Speed,
+    Vorticity,
+    Density,
+}
+
+const VIEWS: [View; 3] = [View::Speed, View::Vorticity, View::Density];
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<View, String> {
+        match s {
+            "speed" => Ok(View::Speed),
+            "vorticity" => Ok(View::Vorticity),
+            "density" => Ok(View::Density),
+            _ => Err(format!("unknown view '{}', expected 'speed', 'vorticity' or 'density'", s)),
+        }
+    }
+}
+
+pub struct LatticeBoltzmannParams {
+    // kinematic viscosity in lattice units, the relaxation time is 3 viscosity + 1/2
+    pub viscosity: f32,
+    // velocity of the inflow in lattice units, should stay well below the speed of sound 0.577
+    pub inflow: f32,
+    // time steps of the fluid per step of the automaton
+    pub iterations: usize,
+    pub scene: Scene,
+    pub view: View,
+}
+
+impl Default for LatticeBoltzmannParams {
+    fn default() -> LatticeBoltzmannParams {
+        LatticeBoltzmannParams {
+            viscosity: 0.02,
+            inflow: 0.1,
+            iterations: 10,
+            scene: Scene::Cylinder,
+            view: View::Vorticity,
+        }
+    }
+}
+
+// Global state of a D2Q9 lattice Boltzmann fluid flowing from left to right. Every time step
+// the distributions relax towards the local equilibrium (BGK collisions) and then move along
+// their velocities. Distributions moving into an obstacle are bounced back. Fluid with the
+// inflow velocity enters through the left, top and bottom edges, the right edge lets the fluid
+// flow out by copying the column next to it.
+pub struct LatticeBoltzmannState {
+    params: LatticeBoltzmannParams,
+    brush_size: usize,
+    max_speed: f64,
+    mean_density: f64,
+    // number of times an unstable flow was started over
+    resets: usize,
+}
+
+impl GlobalState for LatticeBoltzmannState {
+    fn description(&self) -> String {
+        "Lattice Boltzmann fluid (D2Q9)".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let scene = SCENES.iter().position(|s| *s == self.params.scene).unwrap_or(0);
+        let view = VIEWS.iter().position(|v| *v == self.params.view).unwrap_or(0);
+        vec![
+            Param::float("Viscosity", self.params.viscosity as f64, 0.005, 0.2),
+            Param::float("Inflow velocity", self.params.inflow as f64, 0.0, 0.2),
+            Param::int("Iterations per step", self.params.iterations as i64, 1, 50),
+            Param::choice("View", view, &["speed", "vorticity", "density"]),
+            Param::choice("Scene", scene, &["cylinder", "plate", "empty"]),
+            Param::int("Brush size", self.brush_size as i64, 0, 10),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Viscosity" => self.params.viscosity = value as f32,
+            "Inflow velocity" => self.params.inflow = value as f32,
+            "Iterations per step" => self.params.iterations = value as usize,
+            "View" => self.params.view = VIEWS[(value as usize).min(VIEWS.len() - 1)],
+            // used when the automaton is restarted
+            "Scene" => self.params.scene = SCENES[(value as usize).min(SCENES.len() - 1)],
+            "Brush size" => self.brush_size = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Max speed".to_string(), self.max_speed),
+            ("Mean density".to_string(), self.mean_density),
+            ("Instability resets".to_string(), self.resets as f64),
+        ]
+    }
+
+    fn brushes(&self) -> Vec<String> {
+        BRUSHES.iter().map(|b| b.to_string()).collect()
+    }
+}
+
+impl LatticeBoltzmannState {
+    fn update_stats(&mut self, x: &Vec<Vec<Cell>>) {
+        let (mut max_speed, mut density, mut cells) = (0.0f32, 0.0, 0);
+        for cell in x.iter().flatten().filter(|c| !c.obstacle) {
+            let (rho, ux, uy) = cell.moments();
+            max_speed = max_speed.max(ux.hypot(uy));
+            density += rho as f64;
+            cells += 1;
+        }
+        self.max_speed = max_speed as f64;
+        self.mean_density = density / cells.max(1) as f64;
+    }
+
+    fn free_stream(&self) -> Cell {
+        equilibrium(1.0, self.params.inflow, 0.0)
+    }
+}
+
+// Creates a lattice Boltzmann fluid on an n by m grid.
+pub fn new_lattice_boltzmann_automaton(n: usize, m: usize, params: LatticeBoltzmannParams) -> Automaton<Cell, LatticeBoltzmannState> {
+    let state = LatticeBoltzmannState {
+        params: params,
+        brush_size: 2,
+        max_speed: 0.0,
+        mean_density: 0.0,
+        resets: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn).with_paint_fn(paint_fn)
+}
+
+// Shows the speed or the density of the fluid with the colormap, or the vorticity from blue
+// (clockwise) over white to red (counterclockwise). Obstacles are gray.
+pub fn transform(grid: &Vec<Vec<Cell>>, state: &LatticeBoltzmannState) -> FlatImg {
+    let n = grid.len();
+    let m = grid[0].len();
+    let moments: Vec<Vec<(f32, f32, f32)>> = grid.iter().map(|row| row.iter().map(|c| c.moments()).collect()).collect();
+    let mut values = vec![vec![0.0f32; m]; n];
+    for i in 0..n {
+        for j in 0..m {
+            values[i][j] = match state.params.view {
+                View::Speed => moments[i][j].1.hypot(moments[i][j].2),
+                View::Density => moments[i][j].0,
+                // central differences of the velocity, rows grow downwards
+                View::Vorticity if i > 0 && j > 0 && i + 1 < n && j + 1 < m => {
+                    let duy_dx = (moments[i][j + 1].2 - moments[i][j - 1].2) / 2.0;
+                    let dux_dy = (moments[i - 1][j].1 - moments[i + 1][j].1) / 2.0;
+                    duy_dx - dux_dy
+                },
+                View::Vorticity => 0.0,
+            };
+        }
+    }
+
+    let fluid = || grid.iter().flatten().zip(values.iter().flatten()).filter(|(c, _)| !c.obstacle).map(|(_, v)| *v);
+    let (min, max) = (fluid().fold(f32::INFINITY, f32::min), fluid().fold(f32::NEG_INFINITY, f32::max));
+    let mut img = vec![0; n * m * 3];
+    for i in 0..n {
+        for j in 0..m {
+            let v = values[i][j];
+            let rgb = if grid[i][j].obstacle {
+                [90, 90, 90]
+            } else {
+                match state.params.view {
+                    View::Speed => colormap(v / (2.0 * state.params.inflow).max(1e-6)),
+                    View::Density => colormap((v - min) / (max - min).max(1e-6)),
+                    View::Vorticity => {
+                        // scaled so that a quarter of the strongest vorticity is fully saturated
+                        let t = 4.0 * v / max.abs().max(min.abs()).max(1e-6);
+                        if t < 0.0 { interpolate([255, 255, 255], [30, 60, 200], -t) } else { interpolate([255, 255, 255], [200, 30, 30], t) }
+                    },
+                }
+            };
+            img[(i * m + j) * 3..(i * m + j) * 3 + 3].copy_from_slice(&rgb);
+        }
+    }
+    FlatImg { width: m, height: n, img: img }
+}
+
+fn init_fn(n: usize, m: usize, state: &mut LatticeBoltzmannState) -> Vec<Vec<Cell>> {
+    let mut grid = vec![vec![state.free_stream(); m]; n];
+    let (ci, cj) = (n as f32 * 0.52, m as f32 / 4.0);
+    match state.params.scene {
+        Scene::Cylinder => {
+            let r = n as f32 / 12.0;
+            for i in 0..n {
+                for j in 0..m {
+                    let (di, dj) = (i as f32 - ci, j as f32 - cj);
+                    grid[i][j].obstacle = di * di + dj * dj <= r * r;
+                }
+            }
+        },
+        Scene::Plate => {
+            // a plate a sixth of the height long, tilted by 30 degrees
+            let half = n as f32 / 12.0;
+            let steps = (4.0 * half) as usize;
+            for s in 0..=steps {
+                let t = -half + 2.0 * half * s as f32 / steps as f32;
+                let i = (ci - t * 0.866).round() as usize;
+                let j = (cj + t * 0.5).round() as usize;
+                if i < n && j < m {
+                    grid[i][j].obstacle = true;
+                    grid[i][(j + 1).min(m - 1)].obstacle = true;
+                }
+            }
+        },
+        Scene::Empty => {},
+    }
+    state.resets = 0;
+    state.update_stats(&grid);
+    return grid;
+}
+
+// Turns the disk of the brush around (i, j) into obstacles or back into fluid at rest.
+fn paint_fn(x: &mut Vec<Vec<Cell>>, y: &mut LatticeBoltzmannState, i: usize, j: usize, brush: usize) {
+    let n = x.len() as isize;
+    let m = x[0].len() as isize;
+    let r = y.brush_size as isize;
+    for di in -r..=r {
+        for dj in -r..=r {
+            let (ci, cj) = (i as isize + di, j as isize + dj);
+            if di * di + dj * dj > r * r || ci < 0 || cj < 0 || ci >= n || cj >= m {
+                continue;
+            }
+            let cell = &mut x[ci as usize][cj as usize];
+            match brush {
+                0 => cell.obstacle = true,
+                _ => if cell.obstacle {
+                    *cell = equilibrium(1.0, 0.0, 0.0);
+                },
+            }
+        }
+    }
+    y.update_stats(x);
+}
+
+// Relaxes the distributions of the fluid cells towards their local equilibrium (BGK).
+fn collide(x: &mut Vec<Vec<Cell>>, omega: f32) {
+    for cell in x.iter_mut().flatten().filter(|c| !c.obstacle) {
+        let (rho, ux, uy) = cell.moments();
+        let eq = equilibrium(rho, ux, uy);
+        for k in 0..9 {
+            cell.f[k] += omega * (eq.f[k] - cell.f[k]);
+        }
+    }
+}
+
+// Moves the distributions of x along their velocities into next, with bounce-back at obstacles.
+// The lattice wraps around, the edges are overwritten by the boundary conditions afterwards.
+fn stream(x: &Vec<Vec<Cell>>, next: &mut Vec<Vec<Cell>>) {
+    let n = x.len();
+    let m = x[0].len();
+    for i in 0..n {
+        for j in 0..m {
+            if x[i][j].obstacle {
+                continue;
+            }
+            for k in 0..9 {
+                let ni = (i as isize - VELOCITIES[k].1).rem_euclid(n as isize) as usize;
+                let nj = (j as isize + VELOCITIES[k].0).rem_euclid(m as isize) as usize;
+                if x[ni][nj].obstacle {
+                    next[i][j].f[OPPOSITE[k]] = x[i][j].f[k];
+                } else {
+                    next[ni][nj].f[k] = x[i][j].f[k];
+                }
+            }
+        }
+    }
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut LatticeBoltzmannState) {
+    let n = x.len();
+    let m = x[0].len();
+    let omega = 1.0 / (3.0 * y.params.viscosity + 0.5);
+    let free_stream = y.free_stream();
+    let mut next = x.clone();
+    for _ in 0..y.params.iterations {
+        collide(x, omega);
+        stream(x, &mut next);
+
+        // inflow at the left, top and bottom, outflow at the right
+        for i in 0..n {
+            next[i][m - 1].f = next[i][m.saturating_sub(2)].f;
+            if !next[i][0].obstacle {
+                next[i][0] = free_stream;
+            }
+        }
+        for j in 0..m {
+            for i in [0, n - 1] {
+                if !next[i][j].obstacle {
+                    next[i][j] = free_stream;
+                }
+            }
+        }
+        std::mem::swap(x, &mut next);
+    }
+
+    // an unstable flow, e.g. with a too low viscosity for the inflow velocity, is started over
+    if x.iter().flatten().any(|c| !c.f[0].is_finite()) {
+        y.resets += 1;
+        for cell in x.iter_mut().flatten().filter(|c| !c.obstacle) {
+            *cell = free_stream;
+        }
+    }
+    y.update_stats(x);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equilibrium_has_the_given_moments() {
+        for (rho, ux, uy) in [(1.0, 0.0, 0.0), (1.0, 0.1, 0.0), (0.8, -0.05, 0.12), (1.3, 0.15, -0.1)] {
+            let (r, vx, vy) = equilibrium(rho, ux, uy).moments();
+            assert!((r - rho).abs() < 1e-5, "density {} instead of {}", r, rho);
+            assert!((vx - ux).abs() < 1e-5 && (vy - uy).abs() < 1e-5, "velocity ({}, {}) instead of ({}, {})", vx, vy, ux, uy);
+        }
+    }
+
+    #[test]
+    fn collision_and_streaming_conserve_mass() {
+        let (n, m) = (12, 16);
+        let mut x: Vec<Vec<Cell>> = (0..n).map(|i| (0..m).map(|j| {
+            let (a, b) = (i as f32 / n as f32, j as f32 / m as f32);
+            equilibrium(1.0 + 0.2 * a * b, 0.1 * (6.0 * b).sin(), 0.1 * (6.0 * a).cos())
+        }).collect()).collect();
+        let mass = |x: &Vec<Vec<Cell>>| x.iter().flatten().map(|c| c.f.iter().sum::<f32>() as f64).sum::<f64>();
+        let initial = mass(&x);
+        let omega = 1.0 / (3.0 * 0.02 + 0.5);
+        let mut next = x.clone();
+        for _ in 0..100 {
+            collide(&mut x, omega);
+            stream(&x, &mut next);
+            std::mem::swap(&mut x, &mut next);
+        }
+        assert!((mass(&x) - initial).abs() < 1e-3 * initial, "mass {} instead of {}", mass(&x), initial);
+    }
+}
@@ -0,0 +1,475 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::image::{FlatImg, interpolate};
+use crate::rng;
+
+// Cells hold one bit per direction of motion, set if a particle moves in that direction.
+// Direction k points at an angle of k * 360 / D degrees counterclockwise from east, where D is
+// the number of directions of the model. This bit marks walls, which hold no particles.
+const WALL: u8 = 0x80;
+
+const BRUSHES: [&str; 3] = ["Wall", "Gas", "Eraser"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Model {
+    // Hardy, de Pazzis and Pomeau: four directions on the square grid, head-on collisions
+    // turn the pair by 90 degrees
+    Hpp,
+    // Frisch, Hasslacher and Pomeau (FHP-I): six directions on a hexagonal grid, head-on
+    // collisions turn the pair by 60 degrees either way at random and symmetric collisions of
+    // three particles turn them by 60 degrees
+    Fhp,
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Model, String> {
+        match s {
+            "hpp" => Ok(Model::Hpp),
+            "fhp" => Ok(Model::Fhp),
+            _ => Err(format!("unknown model '{}', expected 'hpp' or 'fhp'", s)),
+        }
+    }
+}
+
+impl Model {
+    fn directions(&self) -> usize {
+        match self {
+            Model::Hpp => 4,
+            Model::Fhp => 6,
+        }
+    }
+
+    // Returns the offset (di, dj) of the neighbor in direction k of a cell in row i.
+    // The hexagonal grid is stored with every odd row shifted half a cell to the right.
+    fn offset(&self, i: usize, k: usize) -> (isize, isize) {
+        match self {
+            Model::Hpp => [(0, 1), (-1, 0), (0, -1), (1, 0)][k],
+            Model::Fhp if i.is_multiple_of(2) => [(0, 1), (-1, 0), (-1, -1), (0, -1), (1, -1), (1, 0)][k],
+            Model::Fhp => [(0, 1), (-1, 1), (-1, 0), (0, -1), (1, 0), (1, 1)][k],
+        }
+    }
+
+    // Returns the velocity of a particle moving in direction k, with y pointing up.
+    fn velocity(&self, k: usize) -> (f64, f64) {
+        let angle = 2.0 * std::f64::consts::PI * k as f64 / self.directions() as f64;
+        (angle.cos(), angle.sin())
+    }
+
+    // Returns the collision tables for both chiralities, mapping the particles of a cell to
+    // the particles after the collision.
+    fn collisions(&self) -> [Vec<u8>; 2] {
+        let d = self.directions();
+        let mut tables = [(0..1u8 << d).collect::<Vec<u8>>(), (0..1u8 << d).collect::<Vec<u8>>()];
+        let rotate = |bits: u8, by: usize| ((bits << by) | (bits >> (d - by))) & ((1 << d) - 1);
+        for k in 0..d / 2 {
+            let pair = (1 << k) | (1 << (k + d / 2));
+            match self {
+                Model::Hpp => {
+                    tables[0][pair as usize] = rotate(pair, 1);
+                    tables[1][pair as usize] = rotate(pair, 1);
+                },
+                Model::Fhp => {
+                    tables[0][pair as usize] = rotate(pair, 1);
+                    tables[1][pair as usize] = rotate(pair, d - 1);
+                },
+            }
+        }
+        if *self == Model::Fhp {
+            for table in tables.iter_mut() {
+                table[0b010101] = 0b101010;
+                table[0b101010] = 0b010101;
+            }
+        }
+        return tables;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scene {
+    // a channel with walls at the top and bottom and a plate in the way of the flow
+    Flow,
+    // a square of dense gas in the middle of a thin gas, sending out a sound wave
+    Shock,
+}
+
+impl FromStr for Scene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Scene, String> {
+        match s {
+            "flow" => Ok(Scene::Flow),
+            "shock" => Ok(Scene::Shock),
+            _ => Err(format!("unknown scene '{}', expected 'flow' or 'shock'", s)),
+        }
+    }
+}
+
+pub struct LatticeGasParams {
+    pub model: Model,
+    // probability that a direction of a cell is occupied at the start
+    pub density: f64,
+    // probability per cell and step that a particle moving west is turned east, pushing the gas
+    pub drive: f64,
+    pub scene: Scene,
+    // width of the square blocks over which the velocity field is averaged
+    pub block: usize,
+}
+
+impl Default for LatticeGasParams {
+    fn default() -> LatticeGasParams {
+        LatticeGasParams {
+            model: Model::Fhp,
+            density: 0.2,
+            drive: 0.01,
+            scene: Scene::Flow,
+            block: 8,
+        }
+    }
+}
+
+// Global state of a lattice gas on a torus. Every step particles in the same cell collide,
+// conserving their number and momentum, and then move to the neighbor in their direction.
+// Particles moving into a wall are sent back the way they came.
+// The velocity field is the mean momentum of the particles in square blocks.
+pub struct LatticeGasState {
+    params: LatticeGasParams,
+    // model in use, a change of the parameter takes effect when the automaton is restarted
+    model: Model,
+    collisions: [Vec<u8>; 2],
+    show_velocity: bool,
+    brush_size: usize,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    particles: usize,
+    momentum: (f64, f64),
+}
+
+impl GlobalState for LatticeGasState {
+    fn description(&self) -> String {
+        match self.model {
+            Model::Hpp => "HPP lattice gas".to_string(),
+            Model::Fhp => "FHP lattice gas".to_string(),
+        }
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::choice("Model", (self.params.model == Model::Fhp) as usize, &["HPP", "FHP"]),
+            Param::choice("Scene", (self.params.scene == Scene::Shock) as usize, &["flow", "shock"]),
+            Param::float("Density", self.params.density, 0.0, 1.0),
+            Param::float("Drive", self.params.drive, 0.0, 0.1),
+            Param::choice("Velocity field", self.show_velocity as usize, &["hidden", "shown"]),
+            Param::int("Averaging block", self.params.block as i64, 2, 32),
+            Param::int("Brush size", self.brush_size as i64, 0, 10),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            // used when the automaton is restarted
+            "Model" => self.params.model = if value as usize == 1 { Model::Fhp } else { Model::Hpp },
+            "Scene" => self.params.scene = if value as usize == 1 { Scene::Shock } else { Scene::Flow },
+            "Density" => self.params.density = value,
+            "Drive" => self.params.drive = value,
+            "Velocity field" => self.show_velocity = value as usize == 1,
+            "Averaging block" => self.params.block = value as usize,
+            "Brush size" => self.brush_size = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let particles = self.particles.max(1) as f64;
+        vec![
+            ("Particles".to_string(), self.particles as f64),
+            ("Mean velocity x".to_string(), self.momentum.0 / particles),
+            ("Mean velocity y".to_string(), self.momentum.1 / particles),
+        ]
+    }
+
+    fn brushes(&self) -> Vec<String> {
+        BRUSHES.iter().map(|b| b.to_string()).collect()
+    }
+}
+
+impl LatticeGasState {
+    fn count(&mut self, x: &Vec<Vec<u8>>) {
+        self.particles = 0;
+        self.momentum = (0.0, 0.0);
+        for cell in x.iter().flatten() {
+            if cell & WALL != 0 {
+                continue;
+            }
+            for k in 0..self.model.directions() {
+                if cell & (1 << k) != 0 {
+                    let (vx, vy) = self.model.velocity(k);
+                    self.particles += 1;
+                    self.momentum.0 += vx;
+                    self.momentum.1 += vy;
+                }
+            }
+        }
+    }
+
+    // Returns a cell with every direction occupied with the given probability.
+    fn gas(&mut self, density: f64) -> u8 {
+        let mut cell = 0;
+        for k in 0..self.model.directions() {
+            if self.rng.chance(density) {
+                cell |= 1 << k;
+            }
+        }
+        return cell;
+    }
+}
+
+// Creates a lattice gas on an n by m torus.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_lattice_gas_automaton(n: usize, m: usize, params: LatticeGasParams, seed: Option<u64>) -> Automaton<u8, LatticeGasState> {
+    let state = LatticeGasState {
+        model: params.model,
+        collisions: params.model.collisions(),
+        params: params,
+        show_velocity: true,
+        brush_size: 3,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        particles: 0,
+        momentum: (0.0, 0.0),
+    };
+    Automaton::new(n, m, state, init_fn, next_fn).with_paint_fn(paint_fn)
+}
+
+// Shows the number of particles per cell in shades of gray and walls in blue, with the
+// velocity field drawn as red lines starting at the centers of the blocks.
+pub fn transform(grid: &Vec<Vec<u8>>, state: &LatticeGasState) -> FlatImg {
+    let d = state.model.directions();
+    let mut img = FlatImg::from_2d_vec_with(grid, |cell| {
+        if cell & WALL != 0 {
+            return [40, 60, 150];
+        }
+        interpolate([255, 255, 255], [0, 0, 0], cell.count_ones() as f32 / d as f32)
+    });
+    if !state.show_velocity {
+        return img;
+    }
+
+    let n = grid.len();
+    let m = grid[0].len();
+    let b = state.params.block.max(1);
+    let mut field = vec![];
+    for bi in (0..n).step_by(b) {
+        for bj in (0..m).step_by(b) {
+            let (mut vx, mut vy, mut cells) = (0.0, 0.0, 0);
+            for i in bi..(bi + b).min(n) {
+                for j in bj..(bj + b).min(m) {
+                    cells += 1;
+                    if grid[i][j] & WALL != 0 {
+                        continue;
+                    }
+                    for k in 0..d {
+                        if grid[i][j] & (1 << k) != 0 {
+                            let v = state.model.velocity(k);
+                            vx += v.0;
+                            vy += v.1;
+                        }
+                    }
+                }
+            }
+            field.push((bi + b / 2, bj + b / 2, vx / cells as f64, vy / cells as f64));
+        }
+    }
+    // the fastest block gets a line as long as the block is wide
+    let max = field.iter().map(|(_, _, vx, vy)| vx.hypot(*vy)).fold(0.0, f64::max);
+    if max == 0.0 {
+        return img;
+    }
+    for (ci, cj, vx, vy) in field {
+        let speed = vx.hypot(vy);
+        if speed == 0.0 {
+            continue;
+        }
+        let length = (b as f64 * speed / max).round() as usize;
+        for s in 0..=length {
+            let i = (ci as f64 - vy / speed * s as f64).round();
+            let j = (cj as f64 + vx / speed * s as f64).round();
+            if i < 0.0 || j < 0.0 || i as usize >= n || j as usize >= m {
+                break;
+            }
+            let index = (i as usize * m + j as usize) * 3;
+            let rgb = if s == 0 { [120, 0, 0] } else { [230, 30, 30] };
+            img.img[index..index + 3].copy_from_slice(&rgb);
+        }
+    }
+    return img;
+}
+
+fn init_fn(n: usize, m: usize, state: &mut LatticeGasState) -> Vec<Vec<u8>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    state.model = state.params.model;
+    state.collisions = state.model.collisions();
+    let density = state.params.density;
+    let mut grid = vec![vec![0; m]; n];
+    match state.params.scene {
+        Scene::Flow => {
+            for i in 0..n {
+                for j in 0..m {
+                    grid[i][j] = state.gas(density);
+                }
+            }
+            for j in 0..m {
+                grid[0][j] = WALL;
+                grid[n - 1][j] = WALL;
+            }
+            // a vertical plate across the middle half of the channel
+            for i in n * 3 / 8..n * 5 / 8 {
+                for j in m / 4..m / 4 + 2 {
+                    grid[i][j.min(m - 1)] = WALL;
+                }
+            }
+        },
+        Scene::Shock => {
+            for i in 0..n {
+                for j in 0..m {
+                    let inside = (n * 3 / 8..n * 5 / 8).contains(&i) && (m * 3 / 8..m * 5 / 8).contains(&j);
+                    grid[i][j] = state.gas(if inside { (density * 4.0).min(1.0) } else { density / 2.0 });
+                }
+            }
+        },
+    }
+    // the rows of the hexagonal grid only wrap around consistently if their number is even
+    if state.model == Model::Fhp && n % 2 == 1 {
+        grid[n - 1] = vec![WALL; m];
+    }
+    state.count(&grid);
+    return grid;
+}
+
+// Fills the disk of the brush around (i, j) with walls, random gas or nothing.
+fn paint_fn(x: &mut Vec<Vec<u8>>, y: &mut LatticeGasState, i: usize, j: usize, brush: usize) {
+    let n = x.len() as isize;
+    let m = x[0].len() as isize;
+    let r = y.brush_size as isize;
+    for di in -r..=r {
+        for dj in -r..=r {
+            let (ci, cj) = (i as isize + di, j as isize + dj);
+            if di * di + dj * dj > r * r || ci < 0 || cj < 0 || ci >= n || cj >= m {
+                continue;
+            }
+            let (ci, cj) = (ci as usize, cj as usize);
+            x[ci][cj] = match brush {
+                0 => WALL,
+                1 => y.gas(0.5),
+                _ => 0,
+            };
+        }
+    }
+    y.count(x);
+}
+
+fn next_fn(x: &mut Vec<Vec<u8>>, y: &mut LatticeGasState) {
+    let n = x.len();
+    let m = x[0].len();
+    let d = y.model.directions();
+    let (east, west) = (1, 1 << (d / 2));
+
+    // collisions and driving
+    for i in 0..n {
+        for j in 0..m {
+            let cell = x[i][j];
+            if cell & WALL != 0 {
+                continue;
+            }
+            let chirality = if y.model == Model::Fhp && y.rng.chance(0.5) { 1 } else { 0 };
+            let mut next = y.collisions[chirality][cell as usize];
+            if y.params.drive > 0.0 && next & west != 0 && next & east == 0 && y.rng.chance(y.params.drive) {
+                next = next ^ west ^ east;
+            }
+            x[i][j] = next;
+        }
+    }
+
+    // streaming
+    let mut next = vec![vec![0; m]; n];
+    for i in 0..n {
+        for j in 0..m {
+            let cell = x[i][j];
+            if cell & WALL != 0 {
+                next[i][j] = WALL;
+                continue;
+            }
+            for k in 0..d {
+                if cell & (1 << k) == 0 {
+                    continue;
+                }
+                let (di, dj) = y.model.offset(i, k);
+                let ni = (i as isize + di).rem_euclid(n as isize) as usize;
+                let nj = (j as isize + dj).rem_euclid(m as isize) as usize;
+                if x[ni][nj] & WALL != 0 {
+                    next[i][j] |= 1 << ((k + d / 2) % d);
+                } else {
+                    next[ni][nj] |= 1 << k;
+                }
+            }
+        }
+    }
+    *x = next;
+    y.count(x);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn momentum(model: Model, bits: u8) -> (f64, f64) {
+        (0..model.directions()).filter(|k| bits & (1 << k) != 0).fold((0.0, 0.0), |(x, y), k| {
+            let (vx, vy) = model.velocity(k);
+            (x + vx, y + vy)
+        })
+    }
+
+    #[test]
+    fn collisions_conserve_particles_and_momentum() {
+        for model in [Model::Hpp, Model::Fhp] {
+            for table in model.collisions() {
+                for (before, after) in table.iter().enumerate() {
+                    let before = before as u8;
+                    assert_eq!(before.count_ones(), after.count_ones());
+                    let (p, q) = (momentum(model, before), momentum(model, *after));
+                    assert!((p.0 - q.0).abs() < 1e-9 && (p.1 - q.1).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn head_on_collisions_turn() {
+        for model in [Model::Hpp, Model::Fhp] {
+            let d = model.directions();
+            for table in model.collisions() {
+                for k in 0..d / 2 {
+                    let pair = (1 << k) | (1 << (k + d / 2));
+                    assert_ne!(table[pair], pair as u8);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn opposite_directions_lead_back() {
+        for model in [Model::Hpp, Model::Fhp] {
+            let d = model.directions();
+            for i in 0..4 {
+                for k in 0..d {
+                    let (di, dj) = model.offset(i, k);
+                    let ni = (i as isize + di + 4) as usize % 4;
+                    let (bi, bj) = model.offset(ni, (k + d / 2) % d);
+                    assert_eq!((di + bi, dj + bj), (0, 0));
+                }
+            }
+        }
+    }
+}
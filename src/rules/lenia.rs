@@ -0,0 +1,548 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::fft::{Complex, Fft2};
+use crate::image::{FlatImg, colormap};
+use crate::rng;
+
+// Orbium unicaudatus, the glider of Lenia (Bert Chan), for R = 13, T = 10, mu = 0.15,
+// sigma = 0.015 and a single ring.
+const ORBIUM: [[f32; 20]; 20] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.14, 0.1, 0.0, 0.0, 0.03, 0.03, 0.0, 0.0, 0.3, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.24, 0.3, 0.3, 0.18, 0.14, 0.15, 0.16, 0.15, 0.09, 0.2, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.15, 0.34, 0.44, 0.46, 0.38, 0.18, 0.14, 0.11, 0.13, 0.19, 0.18, 0.45, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.06, 0.13, 0.39, 0.5, 0.5, 0.37, 0.06, 0.0, 0.0, 0.0, 0.02, 0.16, 0.68, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.11, 0.17, 0.17, 0.33, 0.4, 0.38, 0.28, 0.14, 0.0, 0.0, 0.0, 0.0, 0.0, 0.18, 0.42, 0.0, 0.0],
+    [0.0, 0.0, 0.09, 0.18, 0.13, 0.06, 0.08, 0.26, 0.32, 0.32, 0.27, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.82, 0.0, 0.0],
+    [0.27, 0.0, 0.16, 0.12, 0.0, 0.0, 0.0, 0.25, 0.38, 0.44, 0.45, 0.34, 0.0, 0.0, 0.0, 0.0, 0.0, 0.22, 0.17, 0.0],
+    [0.0, 0.07, 0.2, 0.02, 0.0, 0.0, 0.0, 0.31, 0.48, 0.57, 0.6, 0.57, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.49, 0.0],
+    [0.0, 0.59, 0.19, 0.0, 0.0, 0.0, 0.0, 0.2, 0.57, 0.69, 0.76, 0.76, 0.49, 0.0, 0.0, 0.0, 0.0, 0.0, 0.36, 0.0],
+    [0.0, 0.58, 0.19, 0.0, 0.0, 0.0, 0.0, 0.0, 0.67, 0.83, 0.9, 0.92, 0.87, 0.12, 0.0, 0.0, 0.0, 0.0, 0.22, 0.07],
+    [0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.7, 0.93, 1.0, 1.0, 1.0, 0.61, 0.0, 0.0, 0.0, 0.0, 0.18, 0.11],
+    [0.0, 0.0, 0.82, 0.0, 0.0, 0.0, 0.0, 0.0, 0.47, 1.0, 1.0, 0.98, 1.0, 0.96, 0.27, 0.0, 0.0, 0.0, 0.19, 0.1],
+    [0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.25, 1.0, 1.0, 0.84, 0.92, 0.97, 0.54, 0.14, 0.04, 0.1, 0.21, 0.05],
+    [0.0, 0.0, 0.0, 0.4, 0.0, 0.0, 0.0, 0.0, 0.09, 0.8, 1.0, 0.82, 0.8, 0.85, 0.63, 0.31, 0.18, 0.19, 0.2, 0.01],
+    [0.0, 0.0, 0.0, 0.36, 0.1, 0.0, 0.0, 0.0, 0.05, 0.54, 0.86, 0.79, 0.74, 0.72, 0.6, 0.39, 0.28, 0.24, 0.13, 0.0],
+    [0.0, 0.0, 0.0, 0.01, 0.3, 0.07, 0.0, 0.0, 0.08, 0.36, 0.64, 0.7, 0.64, 0.6, 0.51, 0.39, 0.29, 0.19, 0.04, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.1, 0.24, 0.14, 0.1, 0.15, 0.29, 0.45, 0.53, 0.52, 0.46, 0.4, 0.31, 0.21, 0.08, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.21, 0.21, 0.22, 0.29, 0.36, 0.39, 0.37, 0.33, 0.26, 0.18, 0.09, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.03, 0.13, 0.19, 0.22, 0.24, 0.24, 0.23, 0.18, 0.13, 0.05, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.02, 0.06, 0.08, 0.09, 0.07, 0.05, 0.01, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+// A fast shield-shaped glider of the Scutium family, for R = 13, T = 10, mu = 0.29,
+// sigma = 0.043 and a single ring. It moves about a third of a cell per update.
+const SCUTIUM: [[f32; 20]; 20] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.11, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.07, 0.25, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.03, 0.12, 0.17, 0.17, 0.15, 0.59, 0.34, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.18, 0.3, 0.14, 0.26, 0.31, 0.32, 0.33, 0.37, 0.9, 0.7, 0.35, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.37, 0.73, 0.33, 0.33, 0.22, 0.14, 0.14, 0.25, 0.91, 0.97, 0.73, 0.29, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.02, 0.54, 0.9, 0.39, 0.15, 0.01, 0.0, 0.0, 0.0, 0.25, 1.0, 0.98, 0.71, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.17, 0.69, 0.99, 0.36, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.96, 0.37, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.27, 0.81, 1.0, 0.54, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.73, 1.0, 1.0, 0.73, 0.13, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.23, 0.9, 1.0, 0.81, 0.0, 0.0, 0.0, 0.0, 0.0, 0.07, 0.62, 1.0, 1.0, 0.98, 0.38, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.23, 0.95, 1.0, 1.0, 0.21, 0.0, 0.0, 0.0, 0.04, 0.27, 0.69, 1.0, 1.0, 1.0, 0.55, 0.15, 0.0, 0.0],
+    [0.0, 0.0, 0.25, 0.98, 1.0, 1.0, 0.6, 0.28, 0.17, 0.18, 0.28, 0.49, 0.81, 1.0, 1.0, 1.0, 0.64, 0.24, 0.0, 0.0],
+    [0.0, 0.0, 0.24, 0.9, 1.0, 1.0, 0.92, 0.62, 0.48, 0.46, 0.54, 0.71, 0.94, 1.0, 1.0, 1.0, 0.65, 0.28, 0.02, 0.0],
+    [0.0, 0.0, 0.23, 0.73, 1.0, 1.0, 1.0, 0.92, 0.79, 0.75, 0.8, 0.91, 1.0, 1.0, 1.0, 0.99, 0.61, 0.26, 0.03, 0.0],
+    [0.0, 0.0, 0.18, 0.58, 1.0, 1.0, 1.0, 1.0, 0.99, 0.97, 0.98, 1.0, 1.0, 1.0, 1.0, 0.86, 0.52, 0.21, 0.02, 0.0],
+    [0.0, 0.0, 0.09, 0.43, 0.78, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.97, 0.69, 0.4, 0.14, 0.0, 0.0],
+    [0.0, 0.0, 0.03, 0.26, 0.56, 0.84, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.95, 0.75, 0.51, 0.27, 0.06, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.11, 0.33, 0.57, 0.78, 0.93, 1.0, 1.0, 1.0, 0.94, 0.85, 0.71, 0.53, 0.33, 0.13, 0.01, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.01, 0.14, 0.32, 0.49, 0.63, 0.71, 0.74, 0.72, 0.67, 0.58, 0.46, 0.31, 0.14, 0.02, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.01, 0.11, 0.23, 0.34, 0.41, 0.45, 0.44, 0.4, 0.32, 0.22, 0.11, 0.02, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.04, 0.1, 0.15, 0.18, 0.18, 0.15, 0.1, 0.04, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+// Initial patterns together with the parameters they live in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Preset {
+    // a single Orbium in the middle of the grid
+    Orbium,
+    // Orbium at twice the size, with twice the kernel radius
+    LargeOrbium,
+    // several Orbium at random positions and in random directions
+    OrbiumSwarm,
+    // a single Scutium in the middle of the grid
+    Scutium,
+    // random noise in a square in the middle of the grid
+    Soup,
+}
+
+const PRESET_OPTIONS: [&str; 5] = ["Orbium", "large Orbium", "Orbium swarm", "Scutium", "random soup"];
+const PRESETS: [Preset; 5] = [Preset::Orbium, Preset::LargeOrbium, Preset::OrbiumSwarm, Preset::Scutium, Preset::Soup];
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "orbium" => Ok(Preset::Orbium),
+            "large-orbium" => Ok(Preset::LargeOrbium),
+            "swarm" => Ok(Preset::OrbiumSwarm),
+            "scutium" => Ok(Preset::Scutium),
+            "soup" => Ok(Preset::Soup),
+            _ => Err(format!("unknown preset '{}', expected 'orbium', 'large-orbium', 'swarm', 'scutium' or 'soup'", s)),
+        }
+    }
+}
+
+// Parameters of Lenia.
+pub struct LeniaParams {
+    // kernel radius
+    pub radius: usize,
+    // number of updates per unit of time, every update adds 1/T of the growth
+    pub time_resolution: usize,
+    // center and width of the Gaussian growth function
+    pub mu: f64,
+    pub sigma: f64,
+    // heights of the concentric rings of the kernel, from the inside out
+    pub peaks: Vec<f64>,
+}
+
+impl LeniaParams {
+    pub fn from_preset(preset: Preset) -> LeniaParams {
+        LeniaParams {
+            radius: if preset == Preset::LargeOrbium { 26 } else { 13 },
+            time_resolution: 10,
+            mu: if preset == Preset::Scutium { 0.29 } else { 0.15 },
+            sigma: if preset == Preset::Scutium { 0.043 } else { 0.015 },
+            peaks: vec![1.0],
+        }
+    }
+}
+
+// Parses the ring heights of the kernel as comma separated numbers, e.g. "1,0.5".
+pub fn parse_peaks(s: &str) -> Result<Vec<f64>, String> {
+    let peaks = s.split(',')
+        .map(|p| p.trim().parse::<f64>().map_err(|_| format!("invalid ring height '{}'", p)))
+        .collect::<Result<Vec<f64>, String>>()?;
+    if peaks.iter().all(|p| *p <= 0.0) {
+        return Err("at least one ring height must be positive".to_string());
+    }
+    Ok(peaks)
+}
+
+// Global state of Lenia on a torus. Cells hold values in [0, 1]. Every update the grid is
+// convolved with a smooth ring kernel of radius R, the growth function
+//   G(u) = 2 exp(-(u - mu)^2 / (2 sigma^2)) - 1
+// maps the result to [-1, 1] and the cells change by G / T. The convolution is computed with
+// fast Fourier transforms, so its cost does not depend on R.
+pub struct LeniaState {
+    params: LeniaParams,
+    // initial pattern, used when the automaton is restarted
+    preset: Preset,
+    seed: Option<u64>,
+    fft: Option<Fft2>,
+    // transform of the kernel for the current grid size, recomputed when the kernel changes
+    kernel: Vec<Vec<Complex>>,
+    mass: f64,
+    mean_growth: f64,
+}
+
+impl GlobalState for LeniaState {
+    fn description(&self) -> String {
+        "Lenia".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let preset = PRESETS.iter().position(|p| *p == self.preset).unwrap_or(0);
+        vec![
+            Param::choice("Preset", preset, &PRESET_OPTIONS),
+            Param::int("Kernel radius", self.params.radius as i64, 2, 60),
+            Param::int("Time resolution", self.params.time_resolution as i64, 1, 50),
+            Param::float("Growth center", self.params.mu, 0.0, 0.5),
+            Param::float("Growth width", self.params.sigma, 0.001, 0.1),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            // the pattern is used when the automaton is restarted, the parameters it lives in are
+            // set right away
+            "Preset" => {
+                let preset = PRESETS[(value as usize).min(PRESETS.len() - 1)];
+                if preset != self.preset {
+                    self.preset = preset;
+                    self.params = LeniaParams::from_preset(preset);
+                    self.fft = None;
+                }
+            },
+            "Kernel radius" => {
+                self.params.radius = value as usize;
+                self.fft = None;
+            },
+            "Time resolution" => self.params.time_resolution = value as usize,
+            "Growth center" => self.params.mu = value,
+            "Growth width" => self.params.sigma = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Mass".to_string(), self.mass),
+            ("Mean growth".to_string(), self.mean_growth),
+        ]
+    }
+}
+
+impl LeniaState {
+    // Prepares the transforms for an n by m grid if the size or the kernel changed.
+    fn prepare(&mut self, n: usize, m: usize) {
+        if self.fft.as_ref().map(|f| f.size()) == Some((n, m)) {
+            return;
+        }
+        let fft = Fft2::new(n, m);
+        let r = self.params.radius as f64;
+        let peaks = &self.params.peaks;
+        // the kernel is normalized to sum 1 over the grid, also when the radius exceeds half of it
+        self.kernel = fft.normalized_kernel_transform(|di, dj| ring_kernel(((di * di + dj * dj) as f64).sqrt() / r, peaks));
+        self.fft = Some(fft);
+    }
+}
+
+// Value of a kernel with concentric rings of the given heights at distance r (in units of the
+// kernel radius), every ring has the smooth bump profile exp(4 - 1 / (q (1 - q))).
+fn ring_kernel(r: f64, peaks: &Vec<f64>) -> f64 {
+    if r >= 1.0 || peaks.is_empty() {
+        return 0.0;
+    }
+    let br = r * peaks.len() as f64;
+    let ring = (br.floor() as usize).min(peaks.len() - 1);
+    let q = br - ring as f64;
+    if q <= 0.0 || q >= 1.0 {
+        return 0.0;
+    }
+    return peaks[ring] * (4.0 - 1.0 / (q * (1.0 - q))).exp();
+}
+
+// Creates Lenia on an n by m torus, starting with the pattern of the preset.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_lenia_automaton(n: usize, m: usize, params: LeniaParams, preset: Preset, seed: Option<u64>) -> Automaton<f32, LeniaState> {
+    let state = LeniaState {
+        params: params,
+        preset: preset,
+        seed: seed,
+        fft: None,
+        kernel: vec![],
+        mass: 0.0,
+        mean_growth: 0.0,
+    };
+    Automaton::new(n, m, state, lenia_init, lenia_next_fn)
+}
+
+pub fn transform<S>(grid: &Vec<Vec<f32>>, _state: &S) -> FlatImg {
+    FlatImg::from_2d_vec_with(grid, |v| colormap(*v))
+}
+
+// Copies the pattern onto the torus with its top left corner at (i, j), scaled by an integer
+// factor and rotated by the given number of quarter turns.
+fn place(grid: &mut Vec<Vec<f32>>, pattern: &[[f32; 20]; 20], i: usize, j: usize, scale: usize, turns: usize) {
+    let n = grid.len();
+    let m = grid[0].len();
+    let size = 20 * scale;
+    for a in 0..size {
+        for b in 0..size {
+            let (pa, pb) = match turns % 4 {
+                0 => (a, b),
+                1 => (size - 1 - b, a),
+                2 => (size - 1 - a, size - 1 - b),
+                _ => (b, size - 1 - a),
+            };
+            grid[(i + a) % n][(j + b) % m] = pattern[pa / scale][pb / scale];
+        }
+    }
+}
+
+fn lenia_init(n: usize, m: usize, state: &mut LeniaState) -> Vec<Vec<f32>> {
+    let mut rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0.0; m]; n];
+    match state.preset {
+        Preset::Orbium => place(&mut grid, &ORBIUM, (n / 2).saturating_sub(10), (m / 2).saturating_sub(10), 1, 0),
+        Preset::LargeOrbium => place(&mut grid, &ORBIUM, (n / 2).saturating_sub(20), (m / 2).saturating_sub(20), 2, 0),
+        Preset::Scutium => place(&mut grid, &SCUTIUM, (n / 2).saturating_sub(10), (m / 2).saturating_sub(10), 1, 0),
+        Preset::OrbiumSwarm => {
+            for _ in 0..(n * m / 5000).max(2) {
+                place(&mut grid, &ORBIUM, rng.sample(0, n), rng.sample(0, m), 1, rng.sample(0, 4));
+            }
+        },
+        Preset::Soup => {
+            for i in n / 4..n * 3 / 4 {
+                for j in m / 4..m * 3 / 4 {
+                    grid[i][j] = rng.sample(0.0, 1.0);
+                }
+            }
+        },
+    }
+    state.mass = grid.iter().flatten().map(|v| *v as f64).sum();
+    state.mean_growth = 0.0;
+    return grid;
+}
+
+fn lenia_next_fn(x: &mut Vec<Vec<f32>>, y: &mut LeniaState) {
+    let n = x.len();
+    let m = x[0].len();
+    y.prepare(n, m);
+    let fft = y.fft.as_ref().unwrap();
+    let potential = fft.convolve(&fft.transform(x), &y.kernel);
+
+    let dt = 1.0 / y.params.time_resolution.max(1) as f64;
+    let mut mass = 0.0;
+    let mut growth_sum = 0.0;
+    for i in 0..n {
+        for j in 0..m {
+            let d = potential[i][j] - y.params.mu;
+            let growth = 2.0 * (-d * d / (2.0 * y.params.sigma * y.params.sigma)).exp() - 1.0;
+            let v = (x[i][j] as f64 + dt * growth).clamp(0.0, 1.0);
+            x[i][j] = v as f32;
+            mass += v;
+            growth_sum += growth;
+        }
+    }
+    y.mass = mass;
+    y.mean_growth = growth_sum / (n * m) as f64;
+}
+
+// Parameters of SmoothLife. The defaults are the ones of Stephan Rafler's paper, with which
+// gliders emerge from random initial conditions.
+pub struct SmoothLifeParams {
+    // radius of the outer ring, the inner disk has a third of it
+    pub radius: f64,
+    // birth and death intervals of the ring filling
+    pub birth: (f64, f64),
+    pub death: (f64, f64),
+    // smoothness of the steps in the ring filling and in the inner filling
+    pub alpha_n: f64,
+    pub alpha_m: f64,
+    // time step, with 1 the new state is the transition function itself
+    pub dt: f64,
+}
+
+impl Default for SmoothLifeParams {
+    fn default() -> SmoothLifeParams {
+        SmoothLifeParams {
+            radius: 21.0,
+            birth: (0.278, 0.365),
+            death: (0.267, 0.445),
+            alpha_n: 0.028,
+            alpha_m: 0.147,
+            dt: 1.0,
+        }
+    }
+}
+
+// Parses an interval given as two comma separated numbers, e.g. "0.278,0.365".
+pub fn parse_interval(s: &str) -> Result<(f64, f64), String> {
+    match s.split_once(',').map(|(a, b)| (a.trim().parse::<f64>(), b.trim().parse::<f64>())) {
+        Some((Ok(a), Ok(b))) if a <= b => Ok((a, b)),
+        _ => Err(format!("invalid interval '{}', expected two increasing numbers like '0.27,0.36'", s)),
+    }
+}
+
+// Global state of SmoothLife on a torus, a continuous version of the Game of Life. For every
+// cell the filling m of the inner disk and n of the surrounding ring (with anti-aliased edges)
+// are computed, the cell is alive (1) if n is in the birth interval for dead cells (m < 0.5)
+// or in the death interval for live cells (m > 0.5), with smooth steps instead of hard
+// thresholds. With a time step below 1 the cells move towards that value by
+// dt * (2 s(n, m) - 1).
+pub struct SmoothLifeState {
+    params: SmoothLifeParams,
+    seed: Option<u64>,
+    fft: Option<Fft2>,
+    // transforms of the inner disk and of the ring
+    disk: Vec<Vec<Complex>>,
+    ring: Vec<Vec<Complex>>,
+    mass: f64,
+}
+
+impl GlobalState for SmoothLifeState {
+    fn description(&self) -> String {
+        "SmoothLife".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let p = &self.params;
+        vec![
+            Param::float("Outer radius", p.radius, 3.0, 40.0),
+            Param::float("Birth from", p.birth.0, 0.0, 1.0),
+            Param::float("Birth to", p.birth.1, 0.0, 1.0),
+            Param::float("Death from", p.death.0, 0.0, 1.0),
+            Param::float("Death to", p.death.1, 0.0, 1.0),
+            Param::float("Alpha n", p.alpha_n, 0.001, 0.2),
+            Param::float("Alpha m", p.alpha_m, 0.001, 0.5),
+            Param::float("Time step", p.dt, 0.01, 1.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        let p = &mut self.params;
+        match name {
+            "Outer radius" => {
+                p.radius = value;
+                self.fft = None;
+            },
+            "Birth from" => p.birth.0 = value,
+            "Birth to" => p.birth.1 = value,
+            "Death from" => p.death.0 = value,
+            "Death to" => p.death.1 = value,
+            "Alpha n" => p.alpha_n = value,
+            "Alpha m" => p.alpha_m = value,
+            "Time step" => p.dt = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![("Mass".to_string(), self.mass)]
+    }
+}
+
+impl SmoothLifeState {
+    // Prepares the transforms for an n by m grid if the size or the radius changed.
+    fn prepare(&mut self, n: usize, m: usize) {
+        if self.fft.as_ref().map(|f| f.size()) == Some((n, m)) {
+            return;
+        }
+        let fft = Fft2::new(n, m);
+        let ra = self.params.radius;
+        let ri = ra / 3.0;
+        // fraction of the cell at distance d inside a disk of radius r
+        let inside = |d: f64, r: f64| (r + 0.5 - d).clamp(0.0, 1.0);
+        let distance = |di: isize, dj: isize| ((di * di + dj * dj) as f64).sqrt();
+        // both kernels average over their area, as far as it fits on the grid
+        self.disk = fft.normalized_kernel_transform(|di, dj| inside(distance(di, dj), ri));
+        self.ring = fft.normalized_kernel_transform(|di, dj| {
+            let d = distance(di, dj);
+            inside(d, ra) * (1.0 - inside(d, ri))
+        });
+        self.fft = Some(fft);
+    }
+
+    // The transition function s(n, m) of SmoothLife.
+    fn transition(&self, n: f64, m: f64) -> f64 {
+        let p = &self.params;
+        let sigma = |x: f64, a: f64, alpha: f64| 1.0 / (1.0 + (-(x - a) * 4.0 / alpha).exp());
+        let alive = sigma(m, 0.5, p.alpha_m);
+        let low = p.birth.0 * (1.0 - alive) + p.death.0 * alive;
+        let high = p.birth.1 * (1.0 - alive) + p.death.1 * alive;
+        sigma(n, low, p.alpha_n) * (1.0 - sigma(n, high, p.alpha_n))
+    }
+}
+
+// Creates SmoothLife on an n by m torus, starting with random squares of live cells. The
+// discrete rule is chaotic, small grids often die out or end with a single glider.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_smooth_life_automaton(n: usize, m: usize, params: SmoothLifeParams, seed: Option<u64>) -> Automaton<f32, SmoothLifeState> {
+    let state = SmoothLifeState {
+        params: params,
+        seed: seed,
+        fft: None,
+        disk: vec![],
+        ring: vec![],
+        mass: 0.0,
+    };
+    Automaton::new(n, m, state, smooth_life_init, smooth_life_next_fn)
+}
+
+fn smooth_life_init(n: usize, m: usize, state: &mut SmoothLifeState) -> Vec<Vec<f32>> {
+    let mut rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0.0; m]; n];
+    let size = (state.params.radius / 2.0).round().max(1.0) as usize;
+    // about half of the grid is covered by squares of half the outer radius
+    let squares = n * m / (2 * size * size) + 1;
+    for _ in 0..squares {
+        let (i, j) = (rng.sample(0, n), rng.sample(0, m));
+        for a in 0..size {
+            for b in 0..size {
+                grid[(i + a) % n][(j + b) % m] = 1.0;
+            }
+        }
+    }
+    state.mass = grid.iter().flatten().map(|v| *v as f64).sum();
+    return grid;
+}
+
+fn smooth_life_next_fn(x: &mut Vec<Vec<f32>>, y: &mut SmoothLifeState) {
+    let n = x.len();
+    let m = x[0].len();
+    y.prepare(n, m);
+    let fft = y.fft.as_ref().unwrap();
+    let transformed = fft.transform(x);
+    let inner = fft.convolve(&transformed, &y.disk);
+    let outer = fft.convolve(&transformed, &y.ring);
+
+    let dt = y.params.dt;
+    let mut mass = 0.0;
+    for i in 0..n {
+        for j in 0..m {
+            let s = y.transition(outer[i][j], inner[i][j]);
+            let v = if dt >= 1.0 { s } else { (x[i][j] as f64 + dt * (2.0 * s - 1.0)).clamp(0.0, 1.0) };
+            x[i][j] = v as f32;
+            mass += v;
+        }
+    }
+    y.mass = mass;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gliders_keep_their_mass() {
+        for preset in [Preset::Orbium, Preset::Scutium] {
+            let mut aut = new_lenia_automaton(64, 64, LeniaParams::from_preset(preset), preset, Some(1));
+            for _ in 0..100 {
+                aut.next();
+            }
+            let mass = aut.state().1.mass;
+            for _ in 0..200 {
+                aut.next();
+            }
+            assert!(mass > 10.0 && (aut.state().1.mass - mass).abs() < 0.05 * mass);
+        }
+    }
+
+    #[test]
+    fn kernels_larger_than_the_grid_sum_to_one() {
+        // the kernels reach further than half of the grid, a uniform grid must stay uniform
+        let ones = vec![vec![1.0f32; 12]; 16];
+        let mut lenia = new_lenia_automaton(16, 12, LeniaParams::from_preset(Preset::LargeOrbium), Preset::LargeOrbium, Some(1));
+        lenia.next();
+        let lenia = lenia.state().1;
+        let fft = lenia.fft.as_ref().unwrap();
+        let mut potentials = vec![fft.convolve(&fft.transform(&ones), &lenia.kernel)];
+        let mut smooth_life = new_smooth_life_automaton(16, 12, SmoothLifeParams::default(), Some(1));
+        smooth_life.next();
+        let smooth_life = smooth_life.state().1;
+        let fft = smooth_life.fft.as_ref().unwrap();
+        potentials.push(fft.convolve(&fft.transform(&ones), &smooth_life.disk));
+        potentials.push(fft.convolve(&fft.transform(&ones), &smooth_life.ring));
+        for p in potentials.iter().flatten().flatten() {
+            assert!((p - 1.0).abs() < 1e-9, "{} instead of 1", p);
+        }
+    }
+
+    #[test]
+    fn parse_ring_heights() {
+        assert_eq!(parse_peaks("1"), Ok(vec![1.0]));
+        assert_eq!(parse_peaks("0.5, 1,0.667"), Ok(vec![0.5, 1.0, 0.667]));
+        assert_eq!(parse_peaks("0,1"), Ok(vec![0.0, 1.0]));
+        assert!(parse_peaks("").is_err());
+        assert!(parse_peaks("1,,0.5").is_err());
+        assert!(parse_peaks("1;0.5").is_err());
+        // a kernel without a positive ring is empty
+        assert!(parse_peaks("0,-1").is_err());
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(parse_interval("0.278,0.365"), Ok((0.278, 0.365)));
+        assert_eq!(parse_interval(" 0.5 , 0.5 "), Ok((0.5, 0.5)));
+        assert!(parse_interval("0.365,0.278").is_err());
+        assert!(parse_interval("0.278").is_err());
+        assert!(parse_interval("0.278,x").is_err());
+        assert!(parse_interval("0.1,0.2,0.3").is_err());
+    }
+}
@@ -1,11 +1,12 @@
+use std::str::FromStr;
 use crate::rng;
-use crate::automaton::{Automaton, neighbors};
+use crate::automaton::{Automaton, GlobalState, Param, neighbors};
 use crate::image::ToNum;
 
 #[derive(Clone)]
 pub struct Cell {
     t: u64,
-    score: i64,
+    score: f64,
 }
 
 impl ToNum for Cell {
@@ -15,68 +16,244 @@ impl ToNum for Cell {
 }
 
 impl Cell {
-    fn compute_score(&self, rng: &mut rng::UniformRng) -> i64 {
-        return self.score + rng.sample(-5, 5);
+    fn compute_score(&self, noise: f64, rng: &mut rng::UniformRng) -> f64 {
+        return self.score + symmetric_sample(noise, rng);
     }
 }
 
-pub fn new_multi_type_automaton(n: usize, m: usize) -> Automaton<Cell, ()> {
-    Automaton::new(n, m, init_random, elem_next_fn)
+// Returns a uniformly random value in [-range, range).
+fn symmetric_sample(range: f64, rng: &mut rng::UniformRng) -> f64 {
+    if range <= 0.0 {
+        return 0.0;
+    }
+    return rng.sample(-range, range);
+}
+
+// The palette used to display the grid has 10 colors.
+const MAX_TYPES: usize = 10;
+
+// How a cell chooses between types that are equally common among its neighbors.
+#[derive(Clone, Copy)]
+pub enum TieBreak {
+    // uniformly at random
+    Random,
+    // the type with the lowest number
+    Lowest,
+    // the type of the cell itself if it is among them, otherwise uniformly at random
+    KeepCurrent,
+}
+
+const TIE_BREAK_OPTIONS: [&str; 3] = ["random", "lowest", "keep current"];
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TieBreak, String> {
+        match s {
+            "random" => Ok(TieBreak::Random),
+            "lowest" => Ok(TieBreak::Lowest),
+            "keep" => Ok(TieBreak::KeepCurrent),
+            _ => Err(format!("unknown tie-breaking rule '{}', expected 'random', 'lowest' or 'keep'", s)),
+        }
+    }
 }
 
-fn init_random(n: usize, m: usize) -> (Vec<Vec<Cell>>, ()) {
+// Parameters of the multi type model.
+//
+// Every cell converts to the most common type among its neighbors if the best score of that type
+// beats its own score. By default scores never change, with drift, mutation or decay set the scores
+// evolve and the model becomes an evolutionary competition between the types.
+pub struct MultiTypeParams {
+    // number of types, used when the automaton is restarted
+    pub types: usize,
+    // radius of the square neighborhood
+    pub radius: usize,
+    // scores are perturbed by a uniformly random value in [-noise, noise) when compared
+    pub noise: f64,
+    pub tie_break: TieBreak,
+    // every step each score changes by a uniformly random value in [-drift, drift)
+    pub drift: f64,
+    // if set, a converted cell inherits the score of the best cell of its new type
+    // instead of keeping its own score
+    pub inherit: bool,
+    // inherited scores change by a uniformly random value in [-mutation, mutation)
+    pub mutation: f64,
+    // every step each score is multiplied by (1 - decay)
+    pub decay: f64,
+}
+
+impl Default for MultiTypeParams {
+    fn default() -> MultiTypeParams {
+        MultiTypeParams {
+            types: MAX_TYPES,
+            radius: 4,
+            noise: 5.0,
+            tie_break: TieBreak::Random,
+            drift: 0.0,
+            inherit: false,
+            mutation: 0.0,
+            decay: 0.0,
+        }
+    }
+}
+
+pub struct MultiTypeState {
+    params: MultiTypeParams,
+    rng: rng::UniformRng,
+    // number of cells and mean score of every type
+    counts: [usize; MAX_TYPES],
+    mean_scores: [f64; MAX_TYPES],
+}
+
+impl MultiTypeState {
+    fn update_statistics(&mut self, x: &Vec<Vec<Cell>>) {
+        let mut counts = [0; MAX_TYPES];
+        let mut sums = [0.0; MAX_TYPES];
+        for cell in x.iter().flatten() {
+            counts[cell.t as usize] += 1;
+            sums[cell.t as usize] += cell.score;
+        }
+        for t in 0..MAX_TYPES {
+            self.mean_scores[t] = if counts[t] > 0 { sums[t] / counts[t] as f64 } else { 0.0 };
+        }
+        self.counts = counts;
+    }
+}
+
+impl GlobalState for MultiTypeState {
+    fn description(&self) -> String {
+        "Multi type".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let p = &self.params;
+        let tie_break = match p.tie_break {
+            TieBreak::Random => 0,
+            TieBreak::Lowest => 1,
+            TieBreak::KeepCurrent => 2,
+        };
+        vec![
+            Param::int("Types", p.types as i64, 2, MAX_TYPES as i64),
+            Param::int("Radius", p.radius as i64, 1, 10),
+            Param::float("Noise", p.noise, 0.0, 50.0),
+            Param::choice("Tie-breaking", tie_break, &TIE_BREAK_OPTIONS),
+            Param::float("Drift", p.drift, 0.0, 5.0),
+            Param::choice("Inheritance", p.inherit as usize, &["off", "on"]),
+            Param::float("Mutation", p.mutation, 0.0, 5.0),
+            Param::float("Decay", p.decay, 0.0, 0.1),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        let p = &mut self.params;
+        match name {
+            "Types" => p.types = value as usize,
+            "Radius" => p.radius = value as usize,
+            "Noise" => p.noise = value,
+            "Tie-breaking" => p.tie_break = match value as usize {
+                1 => TieBreak::Lowest,
+                2 => TieBreak::KeepCurrent,
+                _ => TieBreak::Random,
+            },
+            "Drift" => p.drift = value,
+            "Inheritance" => p.inherit = value as usize == 1,
+            "Mutation" => p.mutation = value,
+            "Decay" => p.decay = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let mut stats = Vec::new();
+        for t in 0..MAX_TYPES {
+            if self.counts[t] > 0 {
+                stats.push((format!("Type {} cells", t), self.counts[t] as f64));
+                stats.push((format!("Type {} mean score", t), self.mean_scores[t]));
+            }
+        }
+        stats
+    }
+}
+
+// Creates the multi type model. If no seed is given, the random number generator is seeded from entropy.
+pub fn new_multi_type_automaton(n: usize, m: usize, params: MultiTypeParams, seed: Option<u64>) -> Automaton<Cell, MultiTypeState> {
+    let state = MultiTypeState {
+        params: MultiTypeParams { types: params.types.clamp(1, MAX_TYPES), ..params },
+        rng: rng::UniformRng::from_seed(seed),
+        counts: [0; MAX_TYPES],
+        mean_scores: [0.0; MAX_TYPES],
+    };
+    Automaton::new(n, m, state, init_random, elem_next_fn)
+}
+
+fn init_random(n: usize, m: usize, state: &mut MultiTypeState) -> Vec<Vec<Cell>> {
     let mut initial_grid = Vec::new();
-    let mut rng = rng::UniformRng::new();
+    let rng = &mut state.rng;
     for _ in 0..n {
         let mut row = Vec::new();
         for _ in 0..m {
             row.push(Cell {
-                t: rng.sample(0, 10) as u64,
-                score: rng.sample(-5, 5),
+                t: rng.sample(0, state.params.types) as u64,
+                score: rng.sample(-5.0, 5.0),
             });
         }
         initial_grid.push(row);
     }
-    return (initial_grid, ());
+    state.update_statistics(&initial_grid);
+    return initial_grid;
 }
 
-pub fn elem_next_fn(x: &mut Vec<Vec<Cell>>, _: &mut ()) {
-    const N_TYPES: usize = 10;
+pub fn elem_next_fn(x: &mut Vec<Vec<Cell>>, state: &mut MultiTypeState) {
     let n = x.len();
     let m = x[0].len();
+    let params = &state.params;
+    let rng = &mut state.rng;
 
-    let mut next = vec![vec![Cell{t: 0, score: 0}; m]; n];
-    let mut rng = rng::UniformRng::new();
+    let mut next = vec![vec![Cell{t: 0, score: 0.0}; m]; n];
 
-    let mut max_score = [-(1i64<<30); N_TYPES];
+    // best perturbed score of every type and the unperturbed score of the cell that achieved it
+    let mut max_score = [f64::NEG_INFINITY; MAX_TYPES];
+    let mut best_score = [0.0; MAX_TYPES];
     for i in 0..n {
         for j in 0..m {
             let t = x[i][j].t as usize;
-            let score = x[i][j].compute_score(&mut rng);
+            let score = x[i][j].compute_score(params.noise, rng);
             if score > max_score[t] {
                 max_score[t] = score;
+                best_score[t] = x[i][j].score;
             }
         }
     }
 
     for i in 0..n {
         for j in 0..m {
-            let mut count = [0u64; N_TYPES];
-            let nbs = neighbors(i, j, x, 4);
+            let mut count = [0u64; MAX_TYPES];
+            let nbs = neighbors(i, j, x, params.radius);
             for nb in nbs {
                 let t = nb.t as usize;
                 count[t] += 1;
             }
 
             // find most common type among neighbors
+            let current = x[i][j].t as usize;
             let mut max = 0;
             let mut max_c = 0;
-            for i in 0..N_TYPES {
+            let mut ties = 0;
+            for i in 0..MAX_TYPES {
                 if count[i] > max {
                     max = count[i];
                     max_c = i;
-                } else if count[i] == max {
-                    if rng.sample(0, 2) == 0 {
+                    ties = 1;
+                } else if count[i] == max && max > 0 {
+                    ties += 1;
+                    let replace = match params.tie_break {
+                        TieBreak::Lowest => false,
+                        TieBreak::KeepCurrent if max_c == current => false,
+                        TieBreak::KeepCurrent if i == current => true,
+                        // keeps every one of the tied types with the same probability
+                        _ => rng.sample(0, ties) == 0,
+                    };
+                    if replace {
                         max_c = i;
                     }
                 }
@@ -84,12 +261,21 @@ pub fn elem_next_fn(x: &mut Vec<Vec<Cell>>, _: &mut ()) {
 
             // compare score of current cell with max score of cells
             // in most common group among neighbors
-            if max_score[max_c] > x[i][j].compute_score(&mut rng) {
-                next[i][j] = Cell{ t: max_c as u64, ..x[i][j] };
+            if max_score[max_c] > x[i][j].compute_score(params.noise, rng) {
+                let score = if params.inherit {
+                    best_score[max_c] + symmetric_sample(params.mutation, rng)
+                } else {
+                    x[i][j].score
+                };
+                next[i][j] = Cell{ t: max_c as u64, score: score };
             } else {
                 next[i][j] = x[i][j].clone();
             }
+
+            let cell = &mut next[i][j];
+            cell.score = cell.score * (1.0 - params.decay) + symmetric_sample(params.drift, rng);
         }
     }
     *x = next;
-}
\ No newline at end of file
+    state.update_statistics(x);
+}
@@ -0,0 +1,227 @@
+use crate::automaton::{Automaton, GlobalState, Param, append_row};
+use crate::image::{FlatImg, interpolate};
+use crate::rng;
+
+// A site of the spacetime diagram of the Nagel-Schreckenberg model.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Site {
+    Empty,
+    // a car with the given velocity
+    Car(u8),
+    // separates the lanes of a multi-lane road in the diagram
+    Divider,
+}
+
+// Global state of the Nagel-Schreckenberg model.
+// The road is a ring of m cells with one or more lanes, the grid is its spacetime diagram
+// with the lanes shown side by side.
+pub struct NaSchState {
+    // lanes[l][j] is the velocity of the car at position j of lane l
+    lanes: Vec<Vec<Option<u8>>>,
+    n_lanes: usize,
+    vmax: u8,
+    // probability that a car slows down randomly
+    p: f64,
+    // initial fraction of occupied cells
+    density: f64,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // next row of the grid, see append_row
+    row: usize,
+    step: u64,
+    cars: usize,
+    // number of cells moved by all cars in the last step
+    moved: usize,
+    lane_changes: usize,
+}
+
+impl GlobalState for NaSchState {
+    fn description(&self) -> String {
+        "Nagel-Schreckenberg".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("vmax", self.vmax as i64, 1, 10),
+            Param::float("Braking probability", self.p, 0.0, 1.0),
+            Param::float("Density", self.density, 0.0, 1.0),
+            Param::int("Lanes", self.n_lanes as i64, 1, 4),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "vmax" => self.vmax = value as u8,
+            "Braking probability" => self.p = value,
+            // used when the automaton is restarted
+            "Density" => self.density = value,
+            // used when the automaton is restarted
+            "Lanes" => self.n_lanes = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let sites = self.lanes.iter().map(|l| l.len()).sum::<usize>().max(1);
+        let velocity = if self.cars > 0 { self.moved as f64 / self.cars as f64 } else { 0.0 };
+        let mut stats = vec![
+            ("Density".to_string(), self.cars as f64 / sites as f64),
+            ("Flux".to_string(), self.moved as f64 / sites as f64),
+            ("Average velocity".to_string(), velocity),
+        ];
+        if self.lanes.len() > 1 {
+            stats.push(("Lane changes".to_string(), self.lane_changes as f64));
+        }
+        stats
+    }
+}
+
+// Creates the Nagel-Schreckenberg model on a road of length m showing the last n time steps.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_nasch_automaton(n: usize, m: usize, lanes: usize, vmax: u8, p: f64, density: f64, seed: Option<u64>) -> Automaton<Site, NaSchState> {
+    let state = NaSchState {
+        lanes: vec![],
+        n_lanes: lanes.max(1),
+        vmax: vmax,
+        p: p,
+        density: density,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        row: 1,
+        step: 0,
+        cars: 0,
+        moved: 0,
+        lane_changes: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+// Colors cars from red (standing) to green (vmax).
+pub fn transform(grid: &Vec<Vec<Site>>, state: &NaSchState) -> FlatImg {
+    FlatImg::from_2d_vec_with(grid, |site| match site {
+        Site::Empty => [255, 255, 255],
+        Site::Car(v) => interpolate([255, 0, 0], [0, 160, 0], *v as f32 / state.vmax as f32),
+        Site::Divider => [128, 128, 128],
+    })
+}
+
+fn init_fn(n: usize, m: usize, state: &mut NaSchState) -> Vec<Vec<Site>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    state.lanes = vec![vec![None; m]; state.n_lanes];
+    for lane in state.lanes.iter_mut() {
+        for site in lane.iter_mut() {
+            if state.rng.chance(state.density) {
+                *site = Some(0);
+            }
+        }
+    }
+    state.row = 1;
+    state.step = 0;
+    state.cars = state.lanes.iter().flatten().filter(|c| c.is_some()).count();
+    state.moved = 0;
+    state.lane_changes = 0;
+
+    let row = diagram_row(&state.lanes);
+    let mut grid = vec![vec![Site::Empty; row.len()]; n];
+    grid[0] = row;
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<Site>>, y: &mut NaSchState) {
+    if y.lanes.is_empty() || y.lanes[0].is_empty() {
+        return;
+    }
+    if y.lanes.len() > 1 {
+        change_lanes(y);
+    }
+
+    let m = y.lanes[0].len();
+    let vmax = y.vmax;
+    let mut moved = 0;
+    for l in 0..y.lanes.len() {
+        let lane = &y.lanes[l];
+        let mut next = vec![None; m];
+        for j in 0..m {
+            if let Some(v) = lane[j] {
+                // accelerate, keep a safe distance, slow down randomly
+                let mut v = (v + 1).min(vmax) as usize;
+                v = v.min(gap_ahead(lane, j, vmax as usize));
+                if v > 0 && y.rng.chance(y.p) {
+                    v -= 1;
+                }
+                next[(j + v) % m] = Some(v as u8);
+                moved += v;
+            }
+        }
+        y.lanes[l] = next;
+    }
+    y.moved = moved;
+    y.step += 1;
+
+    append_row(x, &mut y.row, diagram_row(&y.lanes));
+}
+
+// Symmetric lane changing: a car that is blocked in its own lane moves to the neighboring lane
+// if it can drive further there and does not cut off a car approaching from behind.
+// To avoid two cars moving into the same cell, cars only change to the lane to their right on even
+// steps and to the lane to their left on odd steps.
+fn change_lanes(y: &mut NaSchState) {
+    let vmax = y.vmax as usize;
+    let mut next = y.lanes.clone();
+    let mut changes = 0;
+    for l in 0..y.lanes.len() {
+        let target = if y.step.is_multiple_of(2) { l + 1 } else { l.wrapping_sub(1) };
+        if target >= y.lanes.len() {
+            continue;
+        }
+        let (own, other) = (&y.lanes[l], &y.lanes[target]);
+        for j in 0..own.len() {
+            if let Some(v) = own[j] {
+                let gap = gap_ahead(own, j, vmax + 1);
+                if gap <= v as usize && other[j].is_none()
+                    && gap_ahead(other, j, vmax + 1) > gap
+                    && gap_behind(other, j, vmax) >= vmax {
+                    next[l][j] = None;
+                    next[target][j] = Some(v);
+                    changes += 1;
+                }
+            }
+        }
+    }
+    y.lanes = next;
+    y.lane_changes = changes;
+}
+
+// Returns the number of empty cells in front of position j, at most `limit`.
+fn gap_ahead(lane: &[Option<u8>], j: usize, limit: usize) -> usize {
+    let m = lane.len();
+    let mut gap = 0;
+    while gap < limit.min(m - 1) && lane[(j + gap + 1) % m].is_none() {
+        gap += 1;
+    }
+    gap
+}
+
+// Returns the number of empty cells behind position j, at most `limit`.
+fn gap_behind(lane: &[Option<u8>], j: usize, limit: usize) -> usize {
+    let m = lane.len();
+    let mut gap = 0;
+    while gap < limit.min(m - 1) && lane[(j + m - gap - 1) % m].is_none() {
+        gap += 1;
+    }
+    gap
+}
+
+fn diagram_row(lanes: &[Vec<Option<u8>>]) -> Vec<Site> {
+    let mut row = Vec::new();
+    for (l, lane) in lanes.iter().enumerate() {
+        if l > 0 {
+            row.push(Site::Divider);
+        }
+        row.extend(lane.iter().map(|c| match c {
+            Some(v) => Site::Car(*v),
+            None => Site::Empty,
+        }));
+    }
+    row
+}
@@ -1,26 +1,390 @@
-use crate::automaton::Automaton;
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param, append_row};
 use crate::rng;
 
-pub fn new_rule30_automaton(n: usize) -> Automaton<u8, (usize, RuleFunction)> {
-    Automaton::new(n, 2*n+1, |n, _| -> (Vec<Vec<u8>>, (usize, RuleFunction)) {
-        let grid = init_middle(n, 2*n+1);
-        return (grid, (1, rule30));
-    }, rule_next_fn)
+// How the first row of a one-dimensional automaton is initialized.
+#[derive(Clone, Copy)]
+pub enum InitialCondition {
+    // a single cell with state 1 in the middle
+    Middle,
+    // every cell is non-zero with the given probability (density),
+    // non-zero cells get a uniformly random state
+    Random(f64),
 }
 
-pub fn new_rule184_automaton(n: usize, m: usize) -> Automaton<u8, (usize, RuleFunction)> {
-    Automaton::new(n, m, |n, m| -> (Vec<Vec<u8>>, (usize, RuleFunction)) {
-        let grid = init_random(n, m);
-        return (grid, (1, rule184));
-    }, rule_next_fn)
+impl FromStr for InitialCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InitialCondition, String> {
+        match s {
+            "middle" => Ok(InitialCondition::Middle),
+            "random" => Ok(InitialCondition::Random(0.5)),
+            _ => Err(format!("unknown initial condition '{}', expected 'middle' or 'random'", s)),
+        }
+    }
+}
+
+// Determines the value of the cells beyond the left and right edge of the grid.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Boundary {
+    // the row wraps around
+    Periodic,
+    // all cells beyond the edges have the given state
+    Fixed(u8),
+    // the row is mirrored at the edges
+    Reflective,
+    // cells beyond the edges are 0 and the grid is widened whenever a non-zero cell
+    // gets close to an edge, so the light cone of a single seed is never clipped
+    Expanding,
+}
+
+const BOUNDARY_OPTIONS: [&str; 5] = ["periodic", "fixed 0", "fixed 1", "reflective", "expanding"];
+
+// Expanding grids stop growing at this width and then behave like Fixed(0).
+const MAX_WIDTH: usize = 2000;
+
+impl Boundary {
+    fn index(&self) -> usize {
+        match self {
+            Boundary::Periodic => 0,
+            Boundary::Fixed(0) => 1,
+            Boundary::Fixed(_) => 2,
+            Boundary::Reflective => 3,
+            Boundary::Expanding => 4,
+        }
+    }
+
+    fn from_index(i: usize) -> Boundary {
+        match i {
+            1 => Boundary::Fixed(0),
+            2 => Boundary::Fixed(1),
+            3 => Boundary::Reflective,
+            4 => Boundary::Expanding,
+            _ => Boundary::Periodic,
+        }
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Boundary, String> {
+        match s {
+            "periodic" => Ok(Boundary::Periodic),
+            "fixed0" => Ok(Boundary::Fixed(0)),
+            "fixed1" => Ok(Boundary::Fixed(1)),
+            "reflective" => Ok(Boundary::Reflective),
+            "expanding" => Ok(Boundary::Expanding),
+            _ => Err(format!("unknown boundary '{}', expected 'periodic', 'fixed0', 'fixed1', 'reflective' or 'expanding'", s)),
+        }
+    }
+}
+
+// Determines how the neighborhood of a cell is mapped to an index into the rule table.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RuleKind {
+    // the states of the 2r+1 cells read as a base k number, leftmost cell first
+    Table,
+    // the sum of the states of the 2r+1 cells
+    Totalistic,
+    // k * (sum of the states of the 2r outer cells) + state of the center cell
+    OuterTotalistic,
+}
+
+impl FromStr for RuleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RuleKind, String> {
+        match s {
+            "table" => Ok(RuleKind::Table),
+            "totalistic" => Ok(RuleKind::Totalistic),
+            "outer-totalistic" => Ok(RuleKind::OuterTotalistic),
+            _ => Err(format!("unknown rule kind '{}', expected 'table', 'totalistic' or 'outer-totalistic'", s)),
+        }
+    }
+}
+
+// A rule of a one-dimensional automaton with k states and neighborhood radius r.
+//
+// Rules are given by their Wolfram code: digit i (base k) of the code is the new value of a cell
+// whose neighborhood has index i, the index is computed as described by RuleKind.
+// Elementary automata are the rules with k=2, r=1 and kind Table.
+pub struct Rule {
+    states: u8,
+    radius: usize,
+    kind: RuleKind,
+    // new value of a cell for every neighborhood index
+    table: Vec<u8>,
+}
+
+impl Rule {
+    pub fn elementary(code: u8) -> Rule {
+        Rule::from_code(2, 1, RuleKind::Table, code as u64).unwrap()
+    }
+
+    pub fn from_code(states: u8, radius: usize, kind: RuleKind, code: u64) -> Result<Rule, String> {
+        let len = check_size(states, radius, kind)?;
+        if let Some(max) = max_code(states, len) {
+            if code > max {
+                return Err(format!("code {} is too large, the maximum is {}", code, max));
+            }
+        }
+        let mut table = vec![0; len];
+        let mut rest = code;
+        for v in table.iter_mut() {
+            *v = (rest % states as u64) as u8;
+            rest /= states as u64;
+        }
+        Ok(Rule { states: states, radius: radius, kind: kind, table: table })
+    }
+
+    // Creates a rule from the digits of its code written out in full, most significant digit first.
+    // This allows rules whose code does not fit into 64 bits.
+    pub fn from_digits(states: u8, radius: usize, kind: RuleKind, digits: &str) -> Result<Rule, String> {
+        let len = check_size(states, radius, kind)?;
+        if digits.len() != len {
+            return Err(format!("expected {} digits, got {}", len, digits.len()));
+        }
+        let mut table = Vec::with_capacity(len);
+        for c in digits.chars().rev() {
+            match c.to_digit(states as u32) {
+                Some(d) => table.push(d as u8),
+                None => return Err(format!("invalid digit '{}' for {} states", c, states)),
+            }
+        }
+        Ok(Rule { states: states, radius: radius, kind: kind, table: table })
+    }
+
+    // Returns the Wolfram code of the rule, if it fits into 64 bits.
+    pub fn code(&self) -> Option<u64> {
+        max_code(self.states, self.table.len())?;
+        let mut code = 0;
+        for v in self.table.iter().rev() {
+            code = code * self.states as u64 + *v as u64;
+        }
+        Some(code)
+    }
+
+    // Returns the new value of the center cell of the given 2r+1 cells.
+    fn apply(&self, neighborhood: &[u8]) -> u8 {
+        let k = self.states as usize;
+        let index = match self.kind {
+            RuleKind::Table => neighborhood.iter().fold(0, |acc, v| acc * k + *v as usize),
+            RuleKind::Totalistic => neighborhood.iter().map(|v| *v as usize).sum(),
+            RuleKind::OuterTotalistic => {
+                let center = neighborhood[self.radius] as usize;
+                let sum: usize = neighborhood.iter().map(|v| *v as usize).sum();
+                k * (sum - center) + center
+            },
+        };
+        self.table[index]
+    }
+
+    fn is_elementary(&self) -> bool {
+        self.states == 2 && self.radius == 1 && self.kind == RuleKind::Table
+    }
+}
+
+// Largest number of entries of a rule table.
+const MAX_TABLE_LEN: usize = 1 << 20;
+
+// Checks the number of states and the radius and returns the length of the rule table.
+// The palette used to display the grid has 10 colors.
+fn check_size(states: u8, radius: usize, kind: RuleKind) -> Result<usize, String> {
+    if !(2..=10).contains(&states) {
+        return Err(format!("number of states must be between 2 and 10, got {}", states));
+    }
+    if radius == 0 {
+        return Err("radius must be at least 1".to_string());
+    }
+    match table_len(states, radius, kind) {
+        Some(len) if len <= MAX_TABLE_LEN => Ok(len),
+        _ => Err(format!("the rule table for {} states and radius {} has more than {} entries", states, radius, MAX_TABLE_LEN)),
+    }
+}
+
+// Returns the length of the rule table, or None if it does not fit into a usize.
+fn table_len(states: u8, radius: usize, kind: RuleKind) -> Option<usize> {
+    let k = states as usize;
+    let cells = radius.checked_mul(2)?.checked_add(1)?;
+    match kind {
+        RuleKind::Table => k.checked_pow(u32::try_from(cells).ok()?),
+        RuleKind::Totalistic => (k - 1).checked_mul(cells)?.checked_add(1),
+        RuleKind::OuterTotalistic => k.checked_mul((k - 1).checked_mul(cells - 1)?.checked_add(1)?),
+    }
+}
+
+// Returns the largest code of a table with the given length, if it fits into 64 bits.
+fn max_code(states: u8, len: usize) -> Option<u64> {
+    let mut count: u64 = 1;
+    for _ in 0..len {
+        count = count.checked_mul(states as u64)?;
+    }
+    Some(count - 1)
+}
+
+// Global state of a one-dimensional cellular automaton.
+// The grid is a spacetime diagram, every row contains the state of the automaton
+// one time step after the row above it.
+pub struct OneDimState {
+    // next row of the grid to compute, see append_row
+    row: usize,
+    initial: InitialCondition,
+    boundary: Boundary,
+    rule: Rule,
+    // number of cars and number of cars that moved in the last step,
+    // only computed for Rule 184 which is a model of traffic flow
+    cars: usize,
+    moving: usize,
+    width: usize,
+}
+
+impl OneDimState {
+    fn is_traffic(&self) -> bool {
+        self.rule.is_elementary() && self.rule.code() == Some(184)
+    }
+}
+
+
+impl GlobalState for OneDimState {
+    fn description(&self) -> String {
+        let code = match self.rule.code() {
+            Some(c) => c.to_string(),
+            None => "(custom table)".to_string(),
+        };
+        if self.rule.is_elementary() {
+            return format!("Rule {}", code);
+        }
+        let kind = match self.rule.kind {
+            RuleKind::Table => "",
+            RuleKind::Totalistic => "totalistic ",
+            RuleKind::OuterTotalistic => "outer totalistic ",
+        };
+        format!("{}k={} r={} code {}", kind, self.rule.states, self.rule.radius, code)
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let mut params = vec![];
+        let max = max_code(self.rule.states, self.rule.table.len());
+        if let (Some(code), Some(max)) = (self.rule.code(), max) {
+            if max <= i64::MAX as u64 {
+                params.push(Param::int("Code", code as i64, 0, max as i64));
+            }
+        }
+        params.push(Param::choice("Boundary", self.boundary.index(), &BOUNDARY_OPTIONS));
+        if let InitialCondition::Random(density) = self.initial {
+            params.push(Param::float("Density", density, 0.0, 1.0));
+        }
+        params
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Code" => {
+                if let Ok(rule) = Rule::from_code(self.rule.states, self.rule.radius, self.rule.kind, value as u64) {
+                    self.rule = rule;
+                }
+            },
+            "Boundary" => self.boundary = Boundary::from_index(value as usize),
+            // used when the automaton is restarted
+            "Density" => self.initial = InitialCondition::Random(value),
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        if !self.is_traffic() || self.width == 0 {
+            return vec![];
+        }
+        let velocity = if self.cars > 0 { self.moving as f64 / self.cars as f64 } else { 0.0 };
+        vec![
+            ("Density".to_string(), self.cars as f64 / self.width as f64),
+            ("Flux".to_string(), self.moving as f64 / self.width as f64),
+            ("Average velocity".to_string(), velocity),
+        ]
+    }
+}
+
+// Creates a one-dimensional automaton with the given rule.
+pub fn new_one_dim_automaton(n: usize, m: usize, rule: Rule, initial: InitialCondition, boundary: Boundary) -> Automaton<u8, OneDimState> {
+    let state = OneDimState {
+        row: 1,
+        initial: initial,
+        boundary: boundary,
+        rule: rule,
+        cars: 0,
+        moving: 0,
+        width: 0,
+    };
+    Automaton::new(n, m, state, init_fn, rule_next_fn)
+}
+
+// Creates the elementary cellular automaton with the given Wolfram code.
+pub fn new_elementary_automaton(n: usize, m: usize, rule: u8, initial: InitialCondition, boundary: Boundary) -> Automaton<u8, OneDimState> {
+    new_one_dim_automaton(n, m, Rule::elementary(rule), initial, boundary)
+}
+
+pub fn new_rule30_automaton(n: usize) -> Automaton<u8, OneDimState> {
+    new_elementary_automaton(n, 2*n+1, 30, InitialCondition::Middle, Boundary::Expanding)
+}
+
+// Rule 184 on a ring is a simple model of traffic flow, every 1 is a car that moves
+// one cell to the right if that cell is empty.
+pub fn new_rule184_automaton(n: usize, m: usize, density: f64) -> Automaton<u8, OneDimState> {
+    new_elementary_automaton(n, m, 184, InitialCondition::Random(density), Boundary::Periodic)
+}
+
+// Measures (density, flux, average velocity) of Rule 184 for the given number of initial densities
+// evenly spaced in [0, 1]. Each measurement runs `steps` steps on a ring of m cells and averages
+// over the second half of the steps, after the initial jams have had time to dissolve.
+pub fn fundamental_diagram(m: usize, steps: usize, points: usize) -> Vec<(f64, f64, f64)> {
+    let mut result = Vec::new();
+    for p in 0..points {
+        let density = if points > 1 { p as f64 / (points - 1) as f64 } else { 0.5 };
+        let mut aut = new_rule184_automaton(2, m, density);
+        let mut moving = 0;
+        let mut measured = 0;
+        for step in 0..steps {
+            aut.next();
+            if step >= steps / 2 {
+                moving += aut.state().1.moving;
+                measured += 1;
+            }
+        }
+        let cars = aut.state().1.cars;
+        let flux = moving as f64 / (measured.max(1) * m) as f64;
+        let velocity = if cars > 0 { moving as f64 / (measured.max(1) * cars) as f64 } else { 0.0 };
+        result.push((cars as f64 / m as f64, flux, velocity));
+    }
+    return result;
 }
 
-fn init_random(n: usize, m: usize) -> Vec<Vec<u8>> {
+// Single seeds are best watched on an expanding grid, random rows on a ring.
+pub fn default_boundary(initial: InitialCondition) -> Boundary {
+    match initial {
+        InitialCondition::Middle => Boundary::Expanding,
+        InitialCondition::Random(_) => Boundary::Periodic,
+    }
+}
+
+fn init_fn(n: usize, m: usize, state: &mut OneDimState) -> Vec<Vec<u8>> {
+    state.row = 1;
+    let grid = match state.initial {
+        InitialCondition::Middle => init_middle(n, m),
+        InitialCondition::Random(density) => init_random(n, m, state.rule.states, density),
+    };
+    state.cars = count_cars(&grid[0]);
+    state.moving = 0;
+    state.width = m;
+    return grid;
+}
+
+fn init_random(n: usize, m: usize, states: u8, density: f64) -> Vec<Vec<u8>> {
     let mut initial_grid = vec![vec![0; m]; n];
     let mut rng = rng::UniformRng::new();
-    for j in 0..m-1 {
-        if rng.sample(0, 2) == 1 {
-            initial_grid[0][j] = 1
+    for j in 0..m {
+        if rng.chance(density) {
+            initial_grid[0][j] = rng.sample(1, states);
         }
     }
     return initial_grid;
@@ -32,37 +396,121 @@ fn init_middle(n: usize, m: usize) -> Vec<Vec<u8>> {
     return initial_grid;
 }
 
-type RuleFunction = fn(u8, u8, u8) -> u64;
-
-fn rule_next_fn(x: &mut Vec<Vec<u8>>, y: &mut (usize, RuleFunction)) {
+// Computes the next row of the spacetime diagram. Once all rows of the grid are filled,
+// the diagram scrolls upward and the automaton keeps evolving with the grid height as the history window.
+fn rule_next_fn(x: &mut Vec<Vec<u8>>, y: &mut OneDimState) {
     let n = x.len();
     let m = x[0].len();
-    let i = y.0;
-    let rf = y.1;
-    if i >= n {
+    let r = y.rule.radius;
+    if n < 2 || m < r {
         return
     }
 
-    for j in 1..m-1 {
-        match rf(x[i-1][j-1], x[i-1][j], x[i-1][j+1]) {
-            1 => x[i][j] = 1,
-            _ => {},
+    let last = &x[y.row - 1];
+    let padded = pad_row(last, r, y.boundary);
+    let mut next_row = vec![0; m];
+    for j in 0..m {
+        next_row[j] = y.rule.apply(&padded[j..j+2*r+1]);
+    }
+    if y.is_traffic() {
+        // a car moved if its cell is empty in the next row
+        y.moving = (0..m).filter(|j| last[*j] == 1 && next_row[*j] == 0).count();
+        y.cars = count_cars(&next_row);
+    }
+    append_row(x, &mut y.row, next_row);
+    y.width = m;
+
+    if y.boundary == Boundary::Expanding && m + 2*r <= MAX_WIDTH {
+        let row = &x[y.row - 1];
+        if row[..r].iter().chain(row[m-r..].iter()).any(|v| *v != 0) {
+            expand(x, r);
+            y.width = m + 2*r;
         }
     }
+}
+
+// Returns the row extended by r cells on both sides according to the boundary.
+fn pad_row(row: &[u8], r: usize, boundary: Boundary) -> Vec<u8> {
+    let m = row.len();
+    let mut padded = Vec::with_capacity(m + 2*r);
+    for j in 0..r {
+        padded.push(match boundary {
+            Boundary::Periodic => row[(m - r % m + j) % m],
+            Boundary::Fixed(v) => v,
+            Boundary::Reflective => row[r-1-j],
+            Boundary::Expanding => 0,
+        });
+    }
+    padded.extend_from_slice(row);
+    for j in 0..r {
+        padded.push(match boundary {
+            Boundary::Periodic => row[j % m],
+            Boundary::Fixed(v) => v,
+            Boundary::Reflective => row[m-1-j],
+            Boundary::Expanding => 0,
+        });
+    }
+    return padded;
+}
 
-    y.0 = i + 1;
+fn count_cars(row: &[u8]) -> usize {
+    row.iter().filter(|v| **v == 1).count()
 }
 
-fn rule30(x: u8, y: u8, z: u8) -> u64 {
-    match (x, y, z) {
-        (1,0,0) | (0,1,1) | (0,1,0) | (0,0,1) => 1,
-        _ => 0,
+// Adds k columns of zeros on both sides of the grid.
+fn expand(x: &mut Vec<Vec<u8>>, k: usize) {
+    for row in x.iter_mut() {
+        let mut expanded = vec![0; k];
+        expanded.append(row);
+        expanded.resize(expanded.len() + k, 0);
+        *row = expanded;
     }
 }
 
-fn rule184(x: u8, y: u8, z: u8) -> u64 {
-    match (x, y, z) {
-        (1,1,1) | (1,0,1) | (1,0,0) | (0,1,1) => 1,
-        _ => 0,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        assert_eq!(Rule::elementary(110).code(), Some(110));
+        let rule = Rule::from_code(3, 1, RuleKind::Totalistic, 1599).unwrap();
+        assert_eq!(rule.table.len(), 7);
+        assert_eq!(rule.code(), Some(1599));
+        let rule = Rule::from_code(2, 2, RuleKind::OuterTotalistic, 1000).unwrap();
+        assert_eq!(rule.table.len(), 10);
+        assert_eq!(rule.code(), Some(1000));
+    }
+
+    #[test]
+    fn digits_match_codes() {
+        // 1599 is 2012020 in base 3
+        let rule = Rule::from_digits(3, 1, RuleKind::Totalistic, "2012020").unwrap();
+        assert_eq!(rule.code(), Some(1599));
+        // the table is indexed by the sum of the neighborhood
+        assert_eq!(rule.apply(&[0, 0, 0]), 0);
+        assert_eq!(rule.apply(&[0, 1, 0]), 2);
+        assert_eq!(rule.apply(&[2, 2, 2]), 2);
+        // a code that does not fit into 64 bits has none
+        let rule = Rule::from_digits(2, 3, RuleKind::Table, &"1".repeat(128)).unwrap();
+        assert_eq!(rule.code(), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        // a totalistic rule with 3 states and radius 1 has 7 digits, the largest code is 3^7 - 1
+        assert!(Rule::from_code(3, 1, RuleKind::Totalistic, 2187).is_err());
+        assert!(Rule::from_digits(3, 1, RuleKind::Totalistic, "201202").is_err());
+        assert!(Rule::from_digits(3, 1, RuleKind::Totalistic, "2013020").is_err());
+        assert!(Rule::from_code(1, 1, RuleKind::Table, 0).is_err());
+        assert!(Rule::from_code(11, 1, RuleKind::Totalistic, 0).is_err());
+        assert!(Rule::from_code(2, 0, RuleKind::Table, 0).is_err());
+        // tables that are too large to allocate or whose length overflows
+        assert!(Rule::from_code(10, 4, RuleKind::Table, 0).is_err());
+        assert!(Rule::from_code(10, 10, RuleKind::Table, 0).is_err());
+        assert!(Rule::from_digits(2, 20, RuleKind::Table, "0").is_err());
+        assert!(Rule::from_code(2, usize::MAX, RuleKind::Totalistic, 0).is_err());
+        assert!(Rule::from_code(10, 9, RuleKind::Totalistic, 0).is_ok());
+        assert!("sum".parse::<RuleKind>().is_err());
+    }
+}
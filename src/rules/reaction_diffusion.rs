@@ -0,0 +1,207 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param, wrap};
+use crate::image::{FlatImg, colormap};
+use crate::rng;
+
+// Concentrations of the two chemicals U and V.
+#[derive(Clone, Copy)]
+pub struct Cell {
+    u: f32,
+    v: f32,
+}
+
+// Feed and kill rates that produce well known patterns.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Preset {
+    Spots,
+    Stripes,
+    Mitosis,
+    Coral,
+}
+
+const PRESETS: [Preset; 4] = [Preset::Spots, Preset::Stripes, Preset::Mitosis, Preset::Coral];
+
+impl Preset {
+    // Returns the feed and kill rates.
+    fn rates(&self) -> (f32, f32) {
+        match self {
+            Preset::Spots => (0.030, 0.062),
+            Preset::Stripes => (0.029, 0.057),
+            Preset::Mitosis => (0.0367, 0.0649),
+            Preset::Coral => (0.0545, 0.062),
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "spots" => Ok(Preset::Spots),
+            "stripes" => Ok(Preset::Stripes),
+            "mitosis" => Ok(Preset::Mitosis),
+            "coral" => Ok(Preset::Coral),
+            _ => Err(format!("unknown preset '{}', expected 'spots', 'stripes', 'mitosis' or 'coral'", s)),
+        }
+    }
+}
+
+// Parameters of the Gray-Scott model.
+pub struct GrayScottParams {
+    pub feed: f32,
+    pub kill: f32,
+    // diffusion rates of U and V
+    pub du: f32,
+    pub dv: f32,
+    // integration steps per step of the automaton
+    pub iterations: usize,
+}
+
+impl GrayScottParams {
+    pub fn from_preset(preset: Preset) -> GrayScottParams {
+        let (feed, kill) = preset.rates();
+        GrayScottParams {
+            feed: feed,
+            kill: kill,
+            du: 1.0,
+            dv: 0.5,
+            iterations: 10,
+        }
+    }
+}
+
+// Global state of the Gray-Scott reaction-diffusion model on a torus. The chemicals react as
+// U + 2V -> 3V, U is fed at rate F and V is removed at rate F + k:
+//   u' = du * lap(u) - u v^2 + F (1 - u)
+//   v' = dv * lap(v) + u v^2 - (F + k) v
+// integrated with explicit Euler steps of size 1 and a nine point Laplacian.
+pub struct GrayScottState {
+    params: GrayScottParams,
+    seed: Option<u64>,
+    mean_u: f64,
+    mean_v: f64,
+}
+
+impl GlobalState for GrayScottState {
+    fn description(&self) -> String {
+        "Gray-Scott reaction-diffusion".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        // the preset that matches the current rates, 0 if they were changed
+        let preset = PRESETS.iter()
+            .position(|p| p.rates() == (self.params.feed, self.params.kill))
+            .map_or(0, |i| i + 1);
+        vec![
+            Param::choice("Preset", preset, &["custom", "spots", "stripes", "mitosis", "coral"]),
+            Param::float("Feed rate", self.params.feed as f64, 0.0, 0.1),
+            Param::float("Kill rate", self.params.kill as f64, 0.0, 0.1),
+            Param::float("Diffusion of U", self.params.du as f64, 0.0, 1.0),
+            Param::float("Diffusion of V", self.params.dv as f64, 0.0, 1.0),
+            Param::int("Iterations per step", self.params.iterations as i64, 1, 50),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Preset" => if let Some(preset) = (value as usize).checked_sub(1).and_then(|i| PRESETS.get(i)) {
+                (self.params.feed, self.params.kill) = preset.rates();
+            },
+            "Feed rate" => self.params.feed = value as f32,
+            "Kill rate" => self.params.kill = value as f32,
+            "Diffusion of U" => self.params.du = value as f32,
+            "Diffusion of V" => self.params.dv = value as f32,
+            "Iterations per step" => self.params.iterations = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Mean U".to_string(), self.mean_u),
+            ("Mean V".to_string(), self.mean_v),
+        ]
+    }
+}
+
+impl GrayScottState {
+    fn update_means(&mut self, x: &Vec<Vec<Cell>>) {
+        let cells = (x.len() * x[0].len()) as f64;
+        self.mean_u = x.iter().flatten().map(|c| c.u as f64).sum::<f64>() / cells;
+        self.mean_v = x.iter().flatten().map(|c| c.v as f64).sum::<f64>() / cells;
+    }
+}
+
+// Creates the Gray-Scott model on an n by m torus.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_gray_scott_automaton(n: usize, m: usize, params: GrayScottParams, seed: Option<u64>) -> Automaton<Cell, GrayScottState> {
+    let state = GrayScottState {
+        params: params,
+        seed: seed,
+        mean_u: 0.0,
+        mean_v: 0.0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+// Shows the concentration of V, scaled from its current minimum to its current maximum.
+pub fn transform(grid: &Vec<Vec<Cell>>, _state: &GrayScottState) -> FlatImg {
+    let min = grid.iter().flatten().map(|c| c.v).fold(f32::INFINITY, f32::min);
+    let max = grid.iter().flatten().map(|c| c.v).fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(1e-6);
+    FlatImg::from_2d_vec_with(grid, |c| colormap((c.v - min) / range))
+}
+
+// Starts with U = 1 and V = 0 except for a few random squares where V is added.
+fn init_fn(n: usize, m: usize, state: &mut GrayScottState) -> Vec<Vec<Cell>> {
+    let mut rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![Cell { u: 1.0, v: 0.0 }; m]; n];
+    let size = (n.min(m) / 10).max(2);
+    let squares = (n * m / 2000).max(1);
+    for _ in 0..squares {
+        let (ci, cj) = (rng.sample(0, n), rng.sample(0, m));
+        for di in 0..size {
+            for dj in 0..size {
+                let cell = &mut grid[(ci + di) % n][(cj + dj) % m];
+                cell.u = 0.5 + rng.sample(-0.05, 0.05);
+                cell.v = 0.25 + rng.sample(-0.05, 0.05);
+            }
+        }
+    }
+    state.update_means(&grid);
+    return grid;
+}
+
+// Weights of the neighbors in the Laplacian, the cell itself has weight -1.
+const LAPLACIAN: [(isize, isize, f32); 8] = [
+    (-1, 0, 0.2), (1, 0, 0.2), (0, -1, 0.2), (0, 1, 0.2),
+    (-1, -1, 0.05), (-1, 1, 0.05), (1, -1, 0.05), (1, 1, 0.05),
+];
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut GrayScottState) {
+    let n = x.len();
+    let m = x[0].len();
+    let p = &y.params;
+    let mut next = x.clone();
+    for _ in 0..p.iterations {
+        for i in 0..n {
+            for j in 0..m {
+                let c = x[i][j];
+                let (mut lap_u, mut lap_v) = (-c.u, -c.v);
+                for (di, dj, w) in LAPLACIAN {
+                    let (ni, nj) = wrap(i, j, di, dj, n, m);
+                    lap_u += w * x[ni][nj].u;
+                    lap_v += w * x[ni][nj].v;
+                }
+                let reaction = c.u * c.v * c.v;
+                next[i][j] = Cell {
+                    u: (c.u + p.du * lap_u - reaction + p.feed * (1.0 - c.u)).clamp(0.0, 1.0),
+                    v: (c.v + p.dv * lap_v + reaction - (p.feed + p.kill) * c.v).clamp(0.0, 1.0),
+                };
+            }
+        }
+        std::mem::swap(x, &mut next);
+    }
+    y.update_means(x);
+}
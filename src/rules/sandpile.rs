@@ -0,0 +1,230 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param};
+use crate::rng;
+use crate::statistics::LogHistogram;
+
+// What a single step of the simulation does.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StepMode {
+    // add one grain and topple until the pile is stable again
+    Grain,
+    // topple all unstable sites once, add a grain if the pile is stable
+    Toppling,
+}
+
+impl FromStr for StepMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StepMode, String> {
+        match s {
+            "grain" => Ok(StepMode::Grain),
+            "toppling" => Ok(StepMode::Toppling),
+            _ => Err(format!("unknown step mode '{}', expected 'grain' or 'toppling'", s)),
+        }
+    }
+}
+
+// Where grains are added.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Placement {
+    Center,
+    Random,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Placement, String> {
+        match s {
+            "center" => Ok(Placement::Center),
+            "random" => Ok(Placement::Random),
+            _ => Err(format!("unknown drop location '{}', expected 'center' or 'random'", s)),
+        }
+    }
+}
+
+// Global state of the Abelian sandpile model (Bak, Tang and Wiesenfeld).
+// Sites with height 4 or more topple and give one grain to each of their von Neumann neighbors,
+// grains falling over the edge of the grid are lost. Repeatedly adding grains drives the pile
+// into a critical state where avalanche sizes and durations follow power laws.
+pub struct SandpileState {
+    mode: StepMode,
+    drop: Placement,
+    // if set, the pile starts with random heights 0..=3 instead of being empty
+    random_start: bool,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // sites that topple in the next sweep
+    unstable: Vec<(usize, usize)>,
+    // topplings and sweeps of the current avalanche
+    size: usize,
+    duration: usize,
+    grains: usize,
+    mean_height: f64,
+    // sizes and durations of all finished avalanches with at least one toppling
+    sizes: LogHistogram,
+    durations: LogHistogram,
+    last_size: usize,
+    last_duration: usize,
+}
+
+impl GlobalState for SandpileState {
+    fn description(&self) -> String {
+        "Abelian sandpile".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::choice("Step", (self.mode == StepMode::Toppling) as usize, &["add grain and relax", "single toppling"]),
+            Param::choice("Drop", (self.drop == Placement::Random) as usize, &["center", "random"]),
+            Param::choice("Start", self.random_start as usize, &["empty", "random"]),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        let second = value as usize == 1;
+        match name {
+            "Step" => self.mode = if second { StepMode::Toppling } else { StepMode::Grain },
+            "Drop" => self.drop = if second { Placement::Random } else { Placement::Center },
+            // used when the automaton is restarted
+            "Start" => self.random_start = second,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let mut stats = vec![
+            ("Grains added".to_string(), self.grains as f64),
+            ("Mean height".to_string(), self.mean_height),
+            ("Avalanches".to_string(), self.sizes.count() as f64),
+            ("Last avalanche size".to_string(), self.last_size as f64),
+            ("Last avalanche duration".to_string(), self.last_duration as f64),
+            ("Mean avalanche size".to_string(), self.sizes.mean()),
+            ("Largest avalanche".to_string(), self.sizes.max() as f64),
+        ];
+        stats.extend(self.sizes.stats("Size"));
+        stats.extend(self.durations.stats("Duration"));
+        stats
+    }
+}
+
+// Creates the sandpile model on an n by m grid.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_sandpile_automaton(n: usize, m: usize, mode: StepMode, drop: Placement, random_start: bool, seed: Option<u64>) -> Automaton<u8, SandpileState> {
+    let state = SandpileState {
+        mode: mode,
+        drop: drop,
+        random_start: random_start,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        unstable: vec![],
+        size: 0,
+        duration: 0,
+        grains: 0,
+        mean_height: 0.0,
+        sizes: LogHistogram::new(),
+        durations: LogHistogram::new(),
+        last_size: 0,
+        last_duration: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+fn init_fn(n: usize, m: usize, state: &mut SandpileState) -> Vec<Vec<u8>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    if state.random_start {
+        for row in grid.iter_mut() {
+            for h in row.iter_mut() {
+                *h = state.rng.sample(0, 4);
+            }
+        }
+    }
+    state.unstable.clear();
+    state.size = 0;
+    state.duration = 0;
+    state.grains = 0;
+    state.sizes = LogHistogram::new();
+    state.durations = LogHistogram::new();
+    state.last_size = 0;
+    state.last_duration = 0;
+    state.mean_height = mean_height(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<u8>>, y: &mut SandpileState) {
+    match y.mode {
+        StepMode::Grain => {
+            if y.unstable.is_empty() {
+                add_grain(x, y);
+            }
+            while !y.unstable.is_empty() {
+                topple(x, y);
+            }
+        },
+        StepMode::Toppling => {
+            if y.unstable.is_empty() {
+                add_grain(x, y);
+            } else {
+                topple(x, y);
+            }
+        },
+    }
+    if y.unstable.is_empty() && y.size > 0 {
+        y.sizes.add(y.size);
+        y.durations.add(y.duration);
+        y.last_size = y.size;
+        y.last_duration = y.duration;
+        y.size = 0;
+        y.duration = 0;
+    }
+    y.mean_height = mean_height(x);
+}
+
+fn add_grain(x: &mut Vec<Vec<u8>>, y: &mut SandpileState) {
+    let n = x.len();
+    let m = x[0].len();
+    let (i, j) = match y.drop {
+        Placement::Center => (n / 2, m / 2),
+        Placement::Random => (y.rng.sample(0, n), y.rng.sample(0, m)),
+    };
+    x[i][j] += 1;
+    y.grains += 1;
+    if x[i][j] >= 4 {
+        y.unstable.push((i, j));
+    }
+}
+
+// Topples every unstable site once and collects the sites that are unstable afterwards.
+fn topple(x: &mut Vec<Vec<u8>>, y: &mut SandpileState) {
+    let n = x.len() as isize;
+    let m = x[0].len() as isize;
+    let offsets = Neighborhood::VonNeumann(1).offsets();
+    let mut next = Vec::new();
+    for (i, j) in std::mem::take(&mut y.unstable) {
+        x[i][j] -= 4;
+        y.size += 1;
+        for (di, dj) in &offsets {
+            let (ni, nj) = (i as isize + di, j as isize + dj);
+            if ni < 0 || nj < 0 || ni >= n || nj >= m {
+                continue;
+            }
+            let (ni, nj) = (ni as usize, nj as usize);
+            x[ni][nj] += 1;
+            // a site is added exactly when it becomes unstable
+            if x[ni][nj] == 4 {
+                next.push((ni, nj));
+            }
+        }
+        if x[i][j] >= 4 {
+            next.push((i, j));
+        }
+    }
+    y.unstable = next;
+    y.duration += 1;
+}
+
+fn mean_height(x: &Vec<Vec<u8>>) -> f64 {
+    let cells = x.len() * x[0].len();
+    x.iter().flatten().map(|h| *h as usize).sum::<usize>() as f64 / cells.max(1) as f64
+}
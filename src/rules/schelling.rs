@@ -0,0 +1,221 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param, neighbors};
+use crate::rng;
+
+// The palette used to display the grid has 10 colors, 0 is used for empty cells.
+const MAX_TYPES: u8 = 9;
+
+// Where an unhappy agent moves to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Relocation {
+    // a uniformly random empty cell
+    Random,
+    // a random empty cell where the agent would be happy, if one is found within a few tries
+    Happy,
+}
+
+impl FromStr for Relocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Relocation, String> {
+        match s {
+            "random" => Ok(Relocation::Random),
+            "happy" => Ok(Relocation::Happy),
+            _ => Err(format!("unknown relocation rule '{}', expected 'random' or 'happy'", s)),
+        }
+    }
+}
+
+// Parameters of Schelling's segregation model.
+// Cells are empty (0) or occupied by an agent of type 1..=K; as with the types of the multi type
+// model, every type is shown with its own color of the palette. An agent is unhappy if the fraction
+// of agents of its own type among its occupied neighbors is below the tolerance. Every step, all
+// unhappy agents move to an empty cell in random order.
+pub struct SchellingParams {
+    // number of types and fraction of empty cells, used when the automaton is restarted
+    pub types: u8,
+    pub empty: f64,
+    pub tolerance: f64,
+    // radius of the square neighborhood
+    pub radius: usize,
+    pub relocation: Relocation,
+}
+
+impl Default for SchellingParams {
+    fn default() -> SchellingParams {
+        SchellingParams {
+            types: 2,
+            empty: 0.1,
+            tolerance: 0.5,
+            radius: 1,
+            relocation: Relocation::Random,
+        }
+    }
+}
+
+pub struct SchellingState {
+    params: SchellingParams,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // mean fraction of like neighbors of all agents with neighbors
+    segregation: f64,
+    unhappy: usize,
+    agents: usize,
+}
+
+impl GlobalState for SchellingState {
+    fn description(&self) -> String {
+        format!("Schelling segregation with {} types", self.params.types)
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::float("Tolerance", self.params.tolerance, 0.0, 1.0),
+            Param::int("Radius", self.params.radius as i64, 1, 5),
+            Param::choice("Move to", (self.params.relocation == Relocation::Happy) as usize, &["random empty cell", "happy empty cell"]),
+            Param::int("Types", self.params.types as i64, 2, MAX_TYPES as i64),
+            Param::float("Empty cells", self.params.empty, 0.01, 0.9),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Tolerance" => self.params.tolerance = value,
+            "Radius" => self.params.radius = value as usize,
+            "Move to" => self.params.relocation = if value as usize == 1 { Relocation::Happy } else { Relocation::Random },
+            "Types" => self.params.types = value as u8,
+            "Empty cells" => self.params.empty = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let unhappy = if self.agents > 0 { self.unhappy as f64 / self.agents as f64 } else { 0.0 };
+        vec![
+            ("Segregation index".to_string(), self.segregation),
+            ("Unhappy agents".to_string(), unhappy),
+        ]
+    }
+}
+
+impl SchellingState {
+    // Returns the fraction of occupied neighbors of (i, j) that have type t, or None if there are none.
+    fn like_fraction(&self, x: &Vec<Vec<u8>>, i: usize, j: usize, t: u8) -> Option<f64> {
+        let mut occupied = 0;
+        let mut like = 0;
+        for nb in neighbors(i, j, x, self.params.radius) {
+            if *nb != 0 {
+                occupied += 1;
+                if *nb == t {
+                    like += 1;
+                }
+            }
+        }
+        if occupied == 0 {
+            return None;
+        }
+        return Some(like as f64 / occupied as f64);
+    }
+
+    // Agents without neighbors are happy.
+    fn is_happy(&self, x: &Vec<Vec<u8>>, i: usize, j: usize, t: u8) -> bool {
+        self.like_fraction(x, i, j, t).is_none_or(|f| f >= self.params.tolerance)
+    }
+
+    fn update_statistics(&mut self, x: &Vec<Vec<u8>>) {
+        let mut sum = 0.0;
+        let mut counted = 0;
+        let mut unhappy = 0;
+        let mut agents = 0;
+        for i in 0..x.len() {
+            for j in 0..x[i].len() {
+                let t = x[i][j];
+                if t == 0 {
+                    continue;
+                }
+                agents += 1;
+                if let Some(f) = self.like_fraction(x, i, j, t) {
+                    sum += f;
+                    counted += 1;
+                    if f < self.params.tolerance {
+                        unhappy += 1;
+                    }
+                }
+            }
+        }
+        self.segregation = if counted > 0 { sum / counted as f64 } else { 0.0 };
+        self.unhappy = unhappy;
+        self.agents = agents;
+    }
+}
+
+// Creates the model with the given number of agent types (at most 9) on an n by m grid.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_schelling_automaton(n: usize, m: usize, params: SchellingParams, seed: Option<u64>) -> Automaton<u8, SchellingState> {
+    let state = SchellingState {
+        params: SchellingParams { types: params.types.clamp(2, MAX_TYPES), radius: params.radius.max(1), ..params },
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        segregation: 0.0,
+        unhappy: 0,
+        agents: 0,
+    };
+    Automaton::new(n, m, state, init_random, next_fn)
+}
+
+fn init_random(n: usize, m: usize, state: &mut SchellingState) -> Vec<Vec<u8>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![0; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            if !state.rng.chance(state.params.empty) {
+                *cell = state.rng.sample(1, state.params.types + 1);
+            }
+        }
+    }
+    state.update_statistics(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<u8>>, y: &mut SchellingState) {
+    let n = x.len();
+    let m = x[0].len();
+
+    let mut unhappy = Vec::new();
+    let mut empty = Vec::new();
+    for i in 0..n {
+        for j in 0..m {
+            if x[i][j] == 0 {
+                empty.push((i, j));
+            } else if !y.is_happy(x, i, j, x[i][j]) {
+                unhappy.push((i, j));
+            }
+        }
+    }
+
+    // shuffle the unhappy agents, so no part of the grid moves first
+    y.rng.shuffle(&mut unhappy);
+
+    for (i, j) in unhappy {
+        if empty.is_empty() {
+            break;
+        }
+        let t = x[i][j];
+        let mut target = y.rng.sample(0, empty.len());
+        if y.params.relocation == Relocation::Happy {
+            for _ in 0..100 {
+                let (ti, tj) = empty[target];
+                if y.is_happy(x, ti, tj, t) {
+                    break;
+                }
+                target = y.rng.sample(0, empty.len());
+            }
+        }
+        let (ti, tj) = empty[target];
+        x[ti][tj] = t;
+        x[i][j] = 0;
+        // the vacated cell takes the place of the occupied one in the list of empty cells
+        empty[target] = (i, j);
+    }
+    y.update_statistics(x);
+}
@@ -0,0 +1,280 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param, wrap};
+use crate::image::{FlatImg, num_to_rgb};
+use crate::rng;
+
+#[derive(Clone)]
+pub struct Cell {
+    strategy: usize,
+    // strategy before the last update, used to highlight changes
+    previous: usize,
+    // total payoff of the last round
+    payoff: f64,
+}
+
+// How a cell chooses the strategy it plays in the next round.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Imitation {
+    // adopt the strategy of the neighbor with the highest payoff (including the cell itself)
+    Best,
+    // compare with one random neighbor and adopt its strategy with probability
+    // 1 / (1 + exp((own payoff - neighbor payoff) / K))
+    Fermi,
+}
+
+const IMITATION_OPTIONS: [&str; 2] = ["best neighbor", "Fermi"];
+
+impl FromStr for Imitation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Imitation, String> {
+        match s {
+            "best" => Ok(Imitation::Best),
+            "fermi" => Ok(Imitation::Fermi),
+            _ => Err(format!("unknown imitation rule '{}', expected 'best' or 'fermi'", s)),
+        }
+    }
+}
+
+// Global state of a spatial game, e.g. the spatial Prisoner's Dilemma of Nowak and May.
+// Every round each cell plays against all of its neighbors, then all cells update their strategy
+// by imitating successful neighbors.
+pub struct GameState {
+    // payoff[a][b] is the payoff of strategy a playing against strategy b
+    payoff: Vec<Vec<f64>>,
+    neighborhood: Neighborhood,
+    // if set, each cell also plays against itself as in the original model of Nowak and May
+    self_interaction: bool,
+    imitation: Imitation,
+    // noise K of the Fermi rule
+    noise: f64,
+    // initial fraction of cells playing strategy 0, the other strategies are equally likely
+    initial_fraction: f64,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    // fraction of cells playing each strategy
+    frequencies: Vec<f64>,
+}
+
+impl GameState {
+    fn update_frequencies(&mut self, x: &Vec<Vec<Cell>>) {
+        let mut counts = vec![0; self.payoff.len()];
+        for cell in x.iter().flatten() {
+            counts[cell.strategy] += 1;
+        }
+        let total = counts.iter().sum::<usize>().max(1) as f64;
+        self.frequencies = counts.iter().map(|c| *c as f64 / total).collect();
+    }
+}
+
+impl GlobalState for GameState {
+    fn description(&self) -> String {
+        format!("Spatial game with {} strategies", self.payoff.len())
+    }
+
+    fn params(&self) -> Vec<Param> {
+        let mut params = Vec::new();
+        for (a, row) in self.payoff.iter().enumerate() {
+            for (b, p) in row.iter().enumerate() {
+                params.push(Param::float(&format!("Payoff {} vs {}", a, b), *p, -5.0, 5.0));
+            }
+        }
+        params.push(Param::choice("Imitation", self.imitation as usize, &IMITATION_OPTIONS));
+        if self.imitation == Imitation::Fermi {
+            params.push(Param::float("Noise K", self.noise, 0.01, 2.0));
+        }
+        params.push(Param::choice("Self-interaction", self.self_interaction as usize, &["off", "on"]));
+        params.push(Param::float("Initial fraction of strategy 0", self.initial_fraction, 0.0, 1.0));
+        params
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Imitation" => self.imitation = if value as usize == 1 { Imitation::Fermi } else { Imitation::Best },
+            "Noise K" => self.noise = value,
+            "Self-interaction" => self.self_interaction = value as usize == 1,
+            // used when the automaton is restarted
+            "Initial fraction of strategy 0" => self.initial_fraction = value,
+            _ => {
+                let s = self.payoff.len();
+                for a in 0..s {
+                    for b in 0..s {
+                        if name == format!("Payoff {} vs {}", a, b) {
+                            self.payoff[a][b] = value;
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        self.frequencies.iter().enumerate()
+            .map(|(s, f)| (format!("Strategy {}", s), *f))
+            .collect()
+    }
+}
+
+// Returns the payoff matrix of the Prisoner's Dilemma as simplified by Nowak and May:
+// strategy 0 cooperates, strategy 1 defects and b > 1 is the temptation to defect.
+pub fn prisoners_dilemma(b: f64) -> Vec<Vec<f64>> {
+    vec![vec![1.0, 0.0], vec![b, 0.0]]
+}
+
+// Parses a square payoff matrix given as rows separated by ';' with entries separated by ',',
+// e.g. "1,0;1.85,0".
+pub fn parse_payoff(s: &str) -> Result<Vec<Vec<f64>>, String> {
+    let mut matrix = Vec::new();
+    for row in s.split(';') {
+        let values: Result<Vec<f64>, _> = row.split(',').map(|v| v.trim().parse::<f64>()).collect();
+        matrix.push(values.map_err(|e| format!("invalid payoff matrix '{}': {}", s, e))?);
+    }
+    let n = matrix.len();
+    // strategies are shown with the colors 2 to 9 of the palette
+    if !(2..=8).contains(&n) || matrix.iter().any(|row| row.len() != n) {
+        return Err(format!("payoff matrix '{}' must be square with 2 to 8 strategies", s));
+    }
+    Ok(matrix)
+}
+
+// Creates a spatial game with the given payoff matrix on an n by m torus.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_game_automaton(n: usize, m: usize, payoff: Vec<Vec<f64>>, neighborhood: Neighborhood, imitation: Imitation, initial_fraction: f64, seed: Option<u64>) -> Automaton<Cell, GameState> {
+    let state = GameState {
+        payoff: payoff,
+        neighborhood: neighborhood,
+        self_interaction: true,
+        imitation: imitation,
+        noise: 0.1,
+        initial_fraction: initial_fraction,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        frequencies: vec![],
+    };
+    Automaton::new(n, m, state, init_random, next_fn)
+}
+
+// For two strategies the colors of Nowak and May are used: blue for cooperators, red for defectors,
+// green for new cooperators and yellow for new defectors.
+// Otherwise every strategy gets its own color of the palette.
+pub fn transform(grid: &Vec<Vec<Cell>>, state: &GameState) -> FlatImg {
+    let two_strategies = state.payoff.len() == 2;
+    FlatImg::from_2d_vec_with(grid, |cell| {
+        if !two_strategies {
+            return num_to_rgb(cell.strategy as u64 + 2);
+        }
+        match (cell.previous, cell.strategy) {
+            (0, 0) => [0, 0, 255],
+            (1, 1) => [255, 0, 0],
+            (1, 0) => [0, 200, 0],
+            _ => [255, 255, 0],
+        }
+    })
+}
+
+fn init_random(n: usize, m: usize, state: &mut GameState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let strategies = state.payoff.len();
+    let mut grid = Vec::new();
+    for _ in 0..n {
+        let mut row = Vec::new();
+        for _ in 0..m {
+            let strategy = if state.rng.chance(state.initial_fraction) {
+                0
+            } else {
+                state.rng.sample(1, strategies)
+            };
+            row.push(Cell { strategy: strategy, previous: strategy, payoff: 0.0 });
+        }
+        grid.push(row);
+    }
+    state.update_frequencies(&grid);
+    return grid;
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut GameState) {
+    let n = x.len();
+    let m = x[0].len();
+    let offsets = y.neighborhood.offsets();
+
+    // play one round
+    for i in 0..n {
+        for j in 0..m {
+            let s = x[i][j].strategy;
+            let mut payoff = if y.self_interaction { y.payoff[s][s] } else { 0.0 };
+            for (di, dj) in &offsets {
+                let (ni, nj) = wrap(i, j, *di, *dj, n, m);
+                payoff += y.payoff[s][x[ni][nj].strategy];
+            }
+            x[i][j].payoff = payoff;
+        }
+    }
+
+    // imitate successful neighbors
+    let mut next = x.clone();
+    for i in 0..n {
+        for j in 0..m {
+            let cell = &x[i][j];
+            let strategy = match y.imitation {
+                Imitation::Best => {
+                    let mut best = cell;
+                    for (di, dj) in &offsets {
+                        let (ni, nj) = wrap(i, j, *di, *dj, n, m);
+                        if x[ni][nj].payoff > best.payoff {
+                            best = &x[ni][nj];
+                        }
+                    }
+                    best.strategy
+                },
+                // without neighbors there is no one to imitate
+                Imitation::Fermi if offsets.is_empty() => cell.strategy,
+                Imitation::Fermi => {
+                    let (di, dj) = offsets[y.rng.sample(0, offsets.len())];
+                    let (ni, nj) = wrap(i, j, di, dj, n, m);
+                    let other = &x[ni][nj];
+                    let p = 1.0 / (1.0 + ((cell.payoff - other.payoff) / y.noise).exp());
+                    if y.rng.chance(p) { other.strategy } else { cell.strategy }
+                },
+            };
+            next[i][j].previous = cell.strategy;
+            next[i][j].strategy = strategy;
+        }
+    }
+    *x = next;
+    y.update_frequencies(x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fermi_imitation_without_neighbors() {
+        let mut aut = new_game_automaton(10, 10, prisoners_dilemma(1.85), Neighborhood::Moore(0), Imitation::Fermi, 0.9, Some(1));
+        let before: Vec<usize> = aut.state().0.iter().flatten().map(|c| c.strategy).collect();
+        aut.next();
+        aut.next();
+        let after: Vec<usize> = aut.state().0.iter().flatten().map(|c| c.strategy).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn parse_payoff_matrices() {
+        assert_eq!(parse_payoff("1, 0; 1.85, 0"), Ok(vec![vec![1.0, 0.0], vec![1.85, 0.0]]));
+        assert_eq!(parse_payoff("0,-1,1;1,0,-1;-1,1,0").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn parse_payoff_rejects_invalid_matrices() {
+        // not a number
+        assert!(parse_payoff("1,x;0,0").is_err());
+        // not square
+        assert!(parse_payoff("1,0,0;0,1,0").is_err());
+        assert!(parse_payoff("1,0;0").is_err());
+        // a single strategy
+        assert!(parse_payoff("1").is_err());
+        // more than 8 strategies
+        let row = ["0"; 9].join(",");
+        assert!(parse_payoff(&[row.as_str(); 9].join(";")).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::str::FromStr;
+use crate::automaton::{Automaton, GlobalState, Param};
+use crate::image::FlatImg;
+use crate::rng;
+
+// The palette used to display the grid has 10 colors.
+const MAX_COLORS: usize = 10;
+
+const ANT_COLOR: [u8; 3] = [255, 0, 255];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Turn {
+    None,
+    Left,
+    Right,
+    Reverse,
+}
+
+impl Turn {
+    // Returns the direction after turning, directions are 0 (up), 1 (right), 2 (down) and 3 (left).
+    fn apply(&self, direction: u8) -> u8 {
+        match self {
+            Turn::None => direction,
+            Turn::Right => (direction + 1) % 4,
+            Turn::Reverse => (direction + 2) % 4,
+            Turn::Left => (direction + 3) % 4,
+        }
+    }
+}
+
+// The transition table of a turmite: for every internal state and color of the cell the turmite
+// is on, the color to write, the turn to make and the next internal state.
+#[derive(Clone)]
+pub struct TurmiteRule {
+    colors: usize,
+    table: Vec<Vec<(u8, Turn, usize)>>,
+}
+
+impl TurmiteRule {
+    // Langton's ant.
+    pub fn langton() -> TurmiteRule {
+        TurmiteRule {
+            colors: 2,
+            table: vec![vec![(1, Turn::Right, 0), (0, Turn::Left, 0)]],
+        }
+    }
+
+    // Parses the rule of an ant with a single state, one letter per color: on a cell of color c
+    // the ant makes the turn given by letter c (L, R, N for none or U), changes the color to c+1
+    // and moves forward. "RL" is Langton's ant.
+    fn from_letters(s: &str) -> Result<TurmiteRule, String> {
+        let colors = s.chars().count();
+        if !(2..=MAX_COLORS).contains(&colors) {
+            return Err(format!("an ant rule needs between 2 and {} letters", MAX_COLORS));
+        }
+        let mut row = Vec::new();
+        for (c, letter) in s.chars().enumerate() {
+            let turn = match letter.to_ascii_uppercase() {
+                'L' => Turn::Left,
+                'R' => Turn::Right,
+                'N' => Turn::None,
+                'U' => Turn::Reverse,
+                other => return Err(format!("unknown turn '{}', expected L, R, N or U", other)),
+            };
+            row.push((((c + 1) % colors) as u8, turn, 0));
+        }
+        Ok(TurmiteRule { colors: colors, table: vec![row] })
+    }
+
+    // Parses a transition table in the notation of Ed Pegg, Jr., a list with one entry per state,
+    // each a list with one {color to write, turn, next state} triple per color. Turns are 1 (none),
+    // 2 (right), 4 (u-turn) and 8 (left). Langton's ant is {{{1,2,0},{0,8,0}}}.
+    fn from_table(s: &str) -> Result<TurmiteRule, String> {
+        let mut states: Vec<Vec<Vec<usize>>> = Vec::new();
+        let mut depth = 0;
+        let mut number = String::new();
+        for ch in s.chars().filter(|c| !c.is_whitespace()) {
+            if ch.is_ascii_digit() {
+                if depth != 3 {
+                    return Err("numbers must be inside {color, turn, state} triples".to_string());
+                }
+                number.push(ch);
+                continue;
+            }
+            if !number.is_empty() {
+                let value = number.parse().map_err(|_| format!("invalid number '{}'", number))?;
+                // depth 3 guarantees there is a current state and triple
+                states.last_mut().unwrap().last_mut().unwrap().push(value);
+                number.clear();
+            }
+            match ch {
+                '{' => {
+                    depth += 1;
+                    match depth {
+                        2 => states.push(Vec::new()),
+                        3 => states.last_mut().unwrap().push(Vec::new()),
+                        1 => {},
+                        _ => return Err("too many nested braces".to_string()),
+                    }
+                },
+                '}' => {
+                    if depth == 0 {
+                        return Err("unbalanced braces".to_string());
+                    }
+                    depth -= 1;
+                },
+                ',' => {},
+                other => return Err(format!("unexpected character '{}'", other)),
+            }
+        }
+        if depth != 0 || states.is_empty() {
+            return Err("unbalanced braces".to_string());
+        }
+
+        let colors = states[0].len();
+        if !(2..=MAX_COLORS).contains(&colors) {
+            return Err(format!("a turmite needs between 2 and {} colors", MAX_COLORS));
+        }
+        let mut table = Vec::new();
+        for entries in &states {
+            if entries.len() != colors {
+                return Err("every state needs an entry for every color".to_string());
+            }
+            let mut row = Vec::new();
+            for entry in entries {
+                if entry.len() != 3 {
+                    return Err("every entry needs a color, a turn and a state".to_string());
+                }
+                let turn = match entry[1] {
+                    1 => Turn::None,
+                    2 => Turn::Right,
+                    4 => Turn::Reverse,
+                    8 => Turn::Left,
+                    other => return Err(format!("unknown turn {}, expected 1, 2, 4 or 8", other)),
+                };
+                if entry[0] >= colors || entry[2] >= states.len() {
+                    return Err("colors and states must be less than their number".to_string());
+                }
+                row.push((entry[0] as u8, turn, entry[2]));
+            }
+            table.push(row);
+        }
+        Ok(TurmiteRule { colors: colors, table: table })
+    }
+}
+
+impl FromStr for TurmiteRule {
+    type Err = String;
+
+    // Parses a rule given as letters (e.g. "RL" or "LLRR") or as a transition table in braces.
+    fn from_str(s: &str) -> Result<TurmiteRule, String> {
+        if s.trim_start().starts_with('{') {
+            TurmiteRule::from_table(s)
+        } else {
+            TurmiteRule::from_letters(s)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Ant {
+    i: usize,
+    j: usize,
+    direction: u8,
+    state: usize,
+}
+
+// Global state of turmites on a torus.
+// Cells hold colors, the ants live in the global state and move one after the other, so unlike
+// the other automata only the cells under the ants change. Every step of the automaton moves
+// every ant a configurable number of times.
+pub struct TurmiteState {
+    rule: TurmiteRule,
+    ants: Vec<Ant>,
+    // number of ants, used when the automaton is restarted
+    ant_count: usize,
+    moves_per_step: usize,
+    seed: Option<u64>,
+    moves: u64,
+    // cells with a color other than 0
+    colored: usize,
+}
+
+impl GlobalState for TurmiteState {
+    fn description(&self) -> String {
+        if self.rule.table.len() == 1 {
+            format!("Ant with {} colors", self.rule.colors)
+        } else {
+            format!("Turmite with {} states and {} colors", self.rule.table.len(), self.rule.colors)
+        }
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("Moves per step", self.moves_per_step as i64, 1, 1000),
+            Param::int("Ants", self.ant_count as i64, 1, 20),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Moves per step" => self.moves_per_step = value as usize,
+            "Ants" => self.ant_count = value as usize,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Moves per ant".to_string(), self.moves as f64),
+            ("Colored cells".to_string(), self.colored as f64),
+        ]
+    }
+}
+
+// Creates turmites with the given rule on an n by m torus.
+// The first ant starts in the middle facing up, further ants start at random cells facing random
+// directions. If no seed is given, the random number generator is seeded from entropy.
+pub fn new_turmite_automaton(n: usize, m: usize, rule: TurmiteRule, ants: usize, moves_per_step: usize, seed: Option<u64>) -> Automaton<u8, TurmiteState> {
+    let state = TurmiteState {
+        rule: rule,
+        ants: vec![],
+        ant_count: ants.max(1),
+        moves_per_step: moves_per_step.max(1),
+        seed: seed,
+        moves: 0,
+        colored: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+// Shows the colors of the cells with the ants on top.
+pub fn transform(grid: &Vec<Vec<u8>>, state: &TurmiteState) -> FlatImg {
+    let mut img = FlatImg::from_2d_vec(grid);
+    for ant in &state.ants {
+        let index = (ant.i * img.width + ant.j) * 3;
+        img.img[index..index + 3].copy_from_slice(&ANT_COLOR);
+    }
+    return img;
+}
+
+fn init_fn(n: usize, m: usize, state: &mut TurmiteState) -> Vec<Vec<u8>> {
+    let mut rng = rng::UniformRng::from_seed(state.seed);
+    state.ants = vec![Ant { i: n / 2, j: m / 2, direction: 0, state: 0 }];
+    for _ in 1..state.ant_count {
+        state.ants.push(Ant { i: rng.sample(0, n), j: rng.sample(0, m), direction: rng.sample(0, 4), state: 0 });
+    }
+    state.moves = 0;
+    state.colored = 0;
+    return vec![vec![0; m]; n];
+}
+
+fn next_fn(x: &mut Vec<Vec<u8>>, y: &mut TurmiteState) {
+    let n = x.len();
+    let m = x[0].len();
+    for _ in 0..y.moves_per_step {
+        for ant in y.ants.iter_mut() {
+            let color = x[ant.i][ant.j] as usize;
+            let (write, turn, next) = y.rule.table[ant.state][color];
+            if color == 0 && write != 0 {
+                y.colored += 1;
+            } else if color != 0 && write == 0 {
+                y.colored -= 1;
+            }
+            x[ant.i][ant.j] = write;
+            ant.state = next;
+            ant.direction = turn.apply(ant.direction);
+            match ant.direction {
+                0 => ant.i = (ant.i + n - 1) % n,
+                1 => ant.j = (ant.j + 1) % m,
+                2 => ant.i = (ant.i + 1) % n,
+                _ => ant.j = (ant.j + m - 1) % m,
+            }
+        }
+        y.moves += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TurmiteRule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn langtons_ant_in_both_notations() {
+        let langton = TurmiteRule::langton();
+        assert!(parse("RL").table == langton.table);
+        assert!(parse("rl").table == langton.table);
+        assert!(parse("{{{1,2,0},{0,8,0}}}").table == langton.table);
+        assert!(parse(" {{ {1, 2, 0}, {0, 8, 0} }}").table == langton.table);
+    }
+
+    #[test]
+    fn parse_tables() {
+        let ant = parse("LLRN");
+        assert_eq!(ant.colors, 4);
+        assert!(ant.table[0][3] == (0, Turn::None, 0));
+        // a fibonacci spiral with two states
+        let turmite = parse("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}");
+        assert_eq!(turmite.colors, 2);
+        assert_eq!(turmite.table.len(), 2);
+        assert!(turmite.table[1][1] == (0, Turn::None, 0));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for rule in ["R", "RLX", "RLRLRLRLRLR", "{{{1,2,0},{0,8,0}}", "{{{1,2,0},{0,8,0}}}}", "{{{1,2},{0,8,0}}}",
+                     "{{{1,3,0},{0,8,0}}}", "{{{2,2,0},{0,8,0}}}", "{{{1,2,1},{0,8,0}}}", "{{{1,2,0},{0,8,0}},{{1,2,0}}}",
+                     "{{{1,2,0},{0,8,0}}}x", "{1,2,0}", "{}"] {
+            assert!(rule.parse::<TurmiteRule>().is_err(), "{} was accepted", rule);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use crate::automaton::{Automaton, GlobalState, Neighborhood, Param, wrap};
+use crate::image::FlatImg;
+use crate::rng;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Water,
+    // a fish with the number of steps since it was born or last bred
+    Fish { age: u32 },
+    // a shark that starves when its energy drops to 0
+    Shark { age: u32, energy: u32 },
+}
+
+// Parameters of Wa-Tor.
+pub struct WatorParams {
+    // steps after which fish and sharks reproduce when they move
+    pub fish_breed: u32,
+    pub shark_breed: u32,
+    // energy of a newborn shark and energy a shark gains by eating a fish,
+    // a shark loses one unit of energy every step
+    pub shark_energy: u32,
+    pub fish_energy: u32,
+    // fractions of cells with fish and sharks at the start, used when the automaton is restarted
+    pub fish_density: f64,
+    pub shark_density: f64,
+}
+
+impl Default for WatorParams {
+    fn default() -> WatorParams {
+        WatorParams {
+            fish_breed: 3,
+            shark_breed: 10,
+            shark_energy: 3,
+            fish_energy: 3,
+            fish_density: 0.3,
+            shark_density: 0.05,
+        }
+    }
+}
+
+// Global state of Dewdney's Wa-Tor on a torus.
+// Every step each animal acts once, in random order:
+// * a fish moves to a random free von Neumann neighbor
+// * a shark moves to a random neighboring fish and eats it, or to a random free neighbor if
+//   there is none, and starves when it runs out of energy
+// An animal that moves after reaching its breeding age leaves a newborn behind.
+//
+// The populations are recorded after every step and, if an output file is given, written to it
+// as CSV. The file is rewritten when the automaton is restarted.
+pub struct WatorState {
+    params: WatorParams,
+    seed: Option<u64>,
+    rng: rng::UniformRng,
+    output_path: Option<String>,
+    output: Option<File>,
+    // steps since the start
+    step: usize,
+    // number of fish and sharks in the last steps, as many as the plot below the grid shows
+    history: VecDeque<(usize, usize)>,
+    // largest numbers of fish and sharks since the start
+    max_fish: usize,
+    max_sharks: usize,
+}
+
+impl GlobalState for WatorState {
+    fn description(&self) -> String {
+        "Wa-Tor".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::int("Fish breeding age", self.params.fish_breed as i64, 1, 30),
+            Param::int("Shark breeding age", self.params.shark_breed as i64, 1, 30),
+            Param::int("Shark energy at birth", self.params.shark_energy as i64, 1, 30),
+            Param::int("Energy per fish", self.params.fish_energy as i64, 1, 30),
+            Param::float("Initial fish", self.params.fish_density, 0.0, 1.0),
+            Param::float("Initial sharks", self.params.shark_density, 0.0, 1.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            "Fish breeding age" => self.params.fish_breed = value as u32,
+            "Shark breeding age" => self.params.shark_breed = value as u32,
+            "Shark energy at birth" => self.params.shark_energy = value as u32,
+            "Energy per fish" => self.params.fish_energy = value as u32,
+            "Initial fish" => self.params.fish_density = value,
+            "Initial sharks" => self.params.shark_density = value,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        let (fish, sharks) = self.history.back().copied().unwrap_or((0, 0));
+        vec![
+            ("Fish".to_string(), fish as f64),
+            ("Sharks".to_string(), sharks as f64),
+            ("Most fish".to_string(), self.max_fish as f64),
+            ("Most sharks".to_string(), self.max_sharks as f64),
+        ]
+    }
+}
+
+impl WatorState {
+    fn record(&mut self, x: &Vec<Vec<Cell>>) {
+        let mut fish = 0;
+        let mut sharks = 0;
+        for cell in x.iter().flatten() {
+            match cell {
+                Cell::Fish { .. } => fish += 1,
+                Cell::Shark { .. } => sharks += 1,
+                Cell::Water => {},
+            }
+        }
+        let step = self.step;
+        self.step += 1;
+        self.max_fish = self.max_fish.max(fish);
+        self.max_sharks = self.max_sharks.max(sharks);
+        while self.history.len() >= x[0].len() {
+            self.history.pop_front();
+        }
+        self.history.push_back((fish, sharks));
+
+        let mut failed = false;
+        if let Some(file) = self.output.as_mut() {
+            failed = writeln!(file, "{},{},{}", step, fish, sharks).is_err();
+        }
+        if failed {
+            println!("could not write to the output file, stopping the export");
+            self.output = None;
+        }
+    }
+
+    // Creates the output file and writes the header, if an output file was given.
+    fn open_output(&mut self) {
+        self.output = None;
+        if let Some(path) = &self.output_path {
+            let file = File::create(path).and_then(|mut f| {
+                writeln!(f, "step,fish,sharks")?;
+                Ok(f)
+            });
+            match file {
+                Ok(f) => self.output = Some(f),
+                Err(e) => println!("could not create output file '{}': {}", path, e),
+            }
+        }
+    }
+}
+
+// Creates Wa-Tor on an n by m torus.
+// If an output path is given, the populations are written to it after every step.
+// If no seed is given, the random number generator is seeded from entropy.
+pub fn new_wator_automaton(n: usize, m: usize, params: WatorParams, output: Option<String>, seed: Option<u64>) -> Automaton<Cell, WatorState> {
+    let state = WatorState {
+        params: params,
+        seed: seed,
+        rng: rng::UniformRng::from_seed(seed),
+        output_path: output,
+        output: None,
+        step: 0,
+        history: VecDeque::new(),
+        max_fish: 0,
+        max_sharks: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn)
+}
+
+// Height of the population plot below the ocean, in rows of the grid.
+fn plot_height(n: usize) -> usize {
+    (n / 3).max(20)
+}
+
+// Shows the ocean (fish green, sharks blue) with the fish and shark populations of the last steps
+// plotted below it, each scaled to its largest value in the plot.
+pub fn transform(grid: &Vec<Vec<Cell>>, state: &WatorState) -> FlatImg {
+    let mut img = FlatImg::from_2d_vec_with(grid, |cell| match cell {
+        Cell::Water => [255, 255, 255],
+        Cell::Fish { .. } => [0, 200, 0],
+        Cell::Shark { .. } => [0, 0, 255],
+    });
+
+    let m = img.width;
+    let h = plot_height(img.height);
+    let mut plot = vec![240; m * h * 3];
+    let shown = &state.history;
+    let max_fish = shown.iter().map(|p| p.0).max().unwrap_or(0).max(1) as f64;
+    let max_sharks = shown.iter().map(|p| p.1).max().unwrap_or(0).max(1) as f64;
+    for (j, (fish, sharks)) in shown.iter().enumerate() {
+        for (count, max, rgb) in [(*fish, max_fish, [0, 200, 0]), (*sharks, max_sharks, [0, 0, 255])] {
+            let i = h - 1 - ((count as f64 / max) * (h - 1) as f64).round() as usize;
+            let index = (i * m + j) * 3;
+            plot[index..index + 3].copy_from_slice(&rgb);
+        }
+    }
+    img.img.extend(plot);
+    img.height += h;
+    return img;
+}
+
+fn init_fn(n: usize, m: usize, state: &mut WatorState) -> Vec<Vec<Cell>> {
+    state.rng = rng::UniformRng::from_seed(state.seed);
+    let mut grid = vec![vec![Cell::Water; m]; n];
+    for row in grid.iter_mut() {
+        for cell in row.iter_mut() {
+            // random ages, so the animals do not all breed in the same step
+            let r = state.rng.sample(0.0, 1.0);
+            if r < state.params.fish_density {
+                *cell = Cell::Fish { age: state.rng.sample(0, state.params.fish_breed.max(1)) };
+            } else if r < state.params.fish_density + state.params.shark_density {
+                *cell = Cell::Shark { age: state.rng.sample(0, state.params.shark_breed.max(1)), energy: state.params.shark_energy };
+            }
+        }
+    }
+    state.step = 0;
+    state.history.clear();
+    state.max_fish = 0;
+    state.max_sharks = 0;
+    state.open_output();
+    state.record(&grid);
+    return grid;
+}
+
+// Returns the von Neumann neighbors of (i, j) for which `select` is true.
+fn free_neighbors<F: Fn(&Cell) -> bool>(x: &Vec<Vec<Cell>>, i: usize, j: usize, select: F) -> Vec<(usize, usize)> {
+    let n = x.len();
+    let m = x[0].len();
+    Neighborhood::VonNeumann(1).offsets().iter()
+        .map(|(di, dj)| wrap(i, j, *di, *dj, n, m))
+        .filter(|(ni, nj)| select(&x[*ni][*nj]))
+        .collect()
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut WatorState) {
+    let n = x.len();
+    let m = x[0].len();
+    let p = &y.params;
+    let rng = &mut y.rng;
+
+    let mut order: Vec<(usize, usize)> = (0..n).flat_map(|i| (0..m).map(move |j| (i, j))).collect();
+    rng.shuffle(&mut order);
+    // cells occupied by an animal that has already acted in this step
+    let mut moved = vec![vec![false; m]; n];
+
+    for (i, j) in order {
+        if moved[i][j] {
+            continue;
+        }
+        let (target, moved_cell, newborn) = match x[i][j] {
+            Cell::Water => continue,
+            Cell::Fish { age } => {
+                let free = free_neighbors(x, i, j, |c| *c == Cell::Water);
+                let target = if free.is_empty() { None } else { Some(free[rng.sample(0, free.len())]) };
+                let breeds = target.is_some() && age + 1 >= p.fish_breed;
+                let cell = Cell::Fish { age: if breeds { 0 } else { age + 1 } };
+                (target, cell, if breeds { Some(Cell::Fish { age: 0 }) } else { None })
+            },
+            Cell::Shark { age, energy } => {
+                let prey = free_neighbors(x, i, j, |c| matches!(c, Cell::Fish { .. }));
+                let (target, energy) = if !prey.is_empty() {
+                    (Some(prey[rng.sample(0, prey.len())]), energy + p.fish_energy)
+                } else {
+                    let free = free_neighbors(x, i, j, |c| *c == Cell::Water);
+                    (if free.is_empty() { None } else { Some(free[rng.sample(0, free.len())]) }, energy)
+                };
+                if energy <= 1 {
+                    x[i][j] = Cell::Water;
+                    continue;
+                }
+                let breeds = target.is_some() && age + 1 >= p.shark_breed;
+                let cell = Cell::Shark { age: if breeds { 0 } else { age + 1 }, energy: energy - 1 };
+                (target, cell, if breeds { Some(Cell::Shark { age: 0, energy: p.shark_energy }) } else { None })
+            },
+        };
+        match target {
+            Some((ti, tj)) => {
+                x[ti][tj] = moved_cell;
+                moved[ti][tj] = true;
+                x[i][j] = newborn.unwrap_or(Cell::Water);
+            },
+            None => x[i][j] = moved_cell,
+        }
+    }
+    y.record(x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_is_limited_to_the_plot_width() {
+        let mut aut = new_wator_automaton(20, 30, WatorParams::default(), None, Some(1));
+        for _ in 0..100 {
+            aut.next();
+        }
+        let state = aut.state().1;
+        assert_eq!(state.step, 101);
+        assert_eq!(state.history.len(), 30);
+        assert!(state.max_fish >= state.history.iter().map(|h| h.0).max().unwrap());
+    }
+}
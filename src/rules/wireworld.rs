@@ -0,0 +1,226 @@
+use crate::automaton::{Automaton, GlobalState, Param, neighbors};
+use crate::image::ToNum;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Conductor,
+    Head,
+    Tail,
+}
+
+impl ToNum for Cell {
+    // black, yellow, blue and red in the palette of num_to_rgb
+    fn to_num(&self) -> u64 {
+        match self {
+            Cell::Empty => 1,
+            Cell::Conductor => 5,
+            Cell::Head => 4,
+            Cell::Tail => 2,
+        }
+    }
+}
+
+// A component that can be stamped onto the grid.
+// Rows of the pattern use '#' for conductors, 'H' for electron heads, 't' for electron tails and
+// '.' for cells that are left unchanged. Signals flow from left to right.
+struct Component {
+    name: &'static str,
+    pattern: &'static [&'static str],
+}
+
+const COMPONENTS: [Component; 6] = [
+    Component {
+        name: "Diode",
+        pattern: &[
+            "....##.......",
+            "#####.#######",
+            "....##.......",
+        ],
+    },
+    Component {
+        name: "Clock (period 8)",
+        pattern: &[
+            ".tH#.......",
+            "#...#######",
+            ".###.......",
+        ],
+    },
+    Component {
+        name: "Clock (period 14)",
+        pattern: &[
+            ".tH####........",
+            "#......########",
+            ".######........",
+        ],
+    },
+    Component {
+        name: "OR gate",
+        pattern: &[
+            "#####.....",
+            ".....#....",
+            "....######",
+            ".....#....",
+            "#####.....",
+        ],
+    },
+    Component {
+        name: "XOR gate",
+        pattern: &[
+            "#####......",
+            ".....#.....",
+            "....####...",
+            "....#..####",
+            "....####...",
+            ".....#.....",
+            "#####......",
+        ],
+    },
+    // A clock with period 14 and one with period 42 in phase feed an XOR gate, which passes two
+    // of every three pulses of the faster clock.
+    Component {
+        name: "Demo: clocks and XOR gate",
+        pattern: &[
+            ".tH####....................................",
+            "#......########################............",
+            ".######........................#...........",
+            "..............................####.........",
+            "..............................#..##########",
+            "..............................####.........",
+            ".tH##################..........#...........",
+            "#....................##########............",
+            ".####################......................",
+        ],
+    },
+];
+
+const SINGLE_CELL_BRUSHES: [(&str, Cell); 4] = [
+    ("Conductor", Cell::Conductor),
+    ("Electron head", Cell::Head),
+    ("Electron tail", Cell::Tail),
+    ("Eraser", Cell::Empty),
+];
+
+// Global state of Wireworld (Brian Silverman). Electron heads become tails, tails become
+// conductors and conductors become heads if one or two of their Moore neighbors are heads.
+// The grid is bounded, cells outside of it are empty.
+pub struct WireworldState {
+    // if set, the grid starts with the demo circuit instead of being empty
+    demo: bool,
+    heads: usize,
+    conductors: usize,
+}
+
+impl GlobalState for WireworldState {
+    fn description(&self) -> String {
+        "Wireworld".to_string()
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param::choice("Start", self.demo as usize, &["empty", "demo circuit"]),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f64) {
+        match name {
+            // used when the automaton is restarted
+            "Start" => self.demo = value as usize == 1,
+            _ => {},
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, f64)> {
+        vec![
+            ("Electrons".to_string(), self.heads as f64),
+            ("Wire cells".to_string(), self.conductors as f64),
+        ]
+    }
+
+    fn brushes(&self) -> Vec<String> {
+        SINGLE_CELL_BRUSHES.iter().map(|(name, _)| name.to_string())
+            .chain(COMPONENTS.iter().map(|c| c.name.to_string()))
+            .collect()
+    }
+}
+
+impl WireworldState {
+    fn count(&mut self, x: &Vec<Vec<Cell>>) {
+        self.heads = x.iter().flatten().filter(|c| **c == Cell::Head).count();
+        self.conductors = x.iter().flatten().filter(|c| **c != Cell::Empty).count();
+    }
+}
+
+// Creates Wireworld on an n by m grid, empty or with the demo circuit in the top left corner.
+// Cells and components of the library can be painted onto the grid.
+pub fn new_wireworld_automaton(n: usize, m: usize, demo: bool) -> Automaton<Cell, WireworldState> {
+    let state = WireworldState {
+        demo: demo,
+        heads: 0,
+        conductors: 0,
+    };
+    Automaton::new(n, m, state, init_fn, next_fn).with_paint_fn(paint_fn)
+}
+
+// Copies the pattern of the component onto the grid with its top left corner at (i, j),
+// parts outside the grid are cut off.
+fn stamp(x: &mut Vec<Vec<Cell>>, component: &Component, i: usize, j: usize) {
+    for (di, row) in component.pattern.iter().enumerate() {
+        for (dj, ch) in row.chars().enumerate() {
+            let cell = match ch {
+                '#' => Cell::Conductor,
+                'H' => Cell::Head,
+                't' => Cell::Tail,
+                _ => continue,
+            };
+            if i + di < x.len() && j + dj < x[0].len() {
+                x[i + di][j + dj] = cell;
+            }
+        }
+    }
+}
+
+fn init_fn(n: usize, m: usize, state: &mut WireworldState) -> Vec<Vec<Cell>> {
+    let mut grid = vec![vec![Cell::Empty; m]; n];
+    if state.demo {
+        stamp(&mut grid, &COMPONENTS[COMPONENTS.len() - 1], 1, 1);
+    }
+    state.count(&grid);
+    return grid;
+}
+
+// Single cell brushes paint the cell at (i, j), components are stamped centered at (i, j).
+fn paint_fn(x: &mut Vec<Vec<Cell>>, y: &mut WireworldState, i: usize, j: usize, brush: usize) {
+    if i >= x.len() || j >= x[0].len() {
+        return;
+    }
+    if brush < SINGLE_CELL_BRUSHES.len() {
+        x[i][j] = SINGLE_CELL_BRUSHES[brush].1;
+    } else if let Some(component) = COMPONENTS.get(brush - SINGLE_CELL_BRUSHES.len()) {
+        let height = component.pattern.len();
+        let width = component.pattern[0].len();
+        stamp(x, component, i.saturating_sub(height / 2), j.saturating_sub(width / 2));
+    }
+    y.count(x);
+}
+
+fn next_fn(x: &mut Vec<Vec<Cell>>, y: &mut WireworldState) {
+    let n = x.len();
+    let m = x[0].len();
+    let mut next = x.clone();
+    for i in 0..n {
+        for j in 0..m {
+            next[i][j] = match x[i][j] {
+                Cell::Head => Cell::Tail,
+                Cell::Tail => Cell::Conductor,
+                Cell::Conductor => {
+                    let heads = neighbors(i, j, x, 1).iter().filter(|c| ***c == Cell::Head).count();
+                    if heads == 1 || heads == 2 { Cell::Head } else { Cell::Conductor }
+                },
+                Cell::Empty => Cell::Empty,
+            };
+        }
+    }
+    *x = next;
+    y.count(x);
+}
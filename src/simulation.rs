@@ -1,14 +1,13 @@
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::{thread, time};
-use std::option::Option;
-use crate::automaton::Automaton;
+use crate::automaton::{Automaton, GlobalState, Param};
 
 // A Simulation can be used to run an Automaton in another thread.
 //
 // Two channels are used to interact with the simulation, an output channel and a command channel.
 // After every step of the automaton, the current simulation state is sent on the output channel.
 // Commands can be sent to the command channel to e.g. start/stop the simulation.
-pub struct Simulation<T: Send, S: Send, U: Send> {
+pub struct Simulation<T: Send, S: Send + GlobalState, U: Send> {
     aut: Automaton<T, S>,
     output_send: Sender<SimulationState<U>>,
     command_recv: Receiver<Command>,
@@ -27,17 +26,26 @@ pub enum Command {
     SingleStep,
     ChangeDelay(std::time::Duration),
     Reset(usize, usize),
+    // Change the parameter with the given name, see GlobalState::params
+    SetParam(String, f64),
+    // Paint the cell in the given row and column with a brush, see GlobalState::brushes
+    Paint(usize, usize, usize),
 }
 
 pub struct SimulationState<U> {
     pub data: U,
     pub step: u64,
     pub running: bool,
+    pub description: String,
+    pub params: Vec<Param>,
+    pub stats: Vec<(String, f64)>,
+    pub brushes: Vec<String>,
 }
 
 pub type TransformFunction<T, S, U> = fn(&Vec<Vec<T>>, &S) -> U;
 
-impl<T: Send, S: Send, U: Send> Simulation<T, S, U> {
+impl<T: Send, S: Send + GlobalState, U: Send> Simulation<T, S, U> {
+    #[allow(clippy::type_complexity)]
     pub fn new(aut: Automaton<T, S>, transform: TransformFunction<T, S, U>, delay: time::Duration) -> (Simulation<T, S, U>, Receiver<SimulationState<U>>, Sender<Command>) {
         let (output_send, output_recv) = channel();
         let (command_send, command_recv) = channel();
@@ -59,6 +67,10 @@ impl<T: Send, S: Send, U: Send> Simulation<T, S, U> {
             data: transformed_state,
             step: self.step,
             running: self.running,
+            description: aut_state.1.description(),
+            params: aut_state.1.params(),
+            stats: aut_state.1.stats(),
+            brushes: aut_state.1.brushes(),
         };
 
         match self.output_send.send(simulation_state) {
@@ -67,44 +79,52 @@ impl<T: Send, S: Send, U: Send> Simulation<T, S, U> {
         }
     }
 
-    fn recv_command(&mut self) {
-        let command: Option<Command>;
+    fn recv_commands(&mut self) {
         if self.running {
-            command = match self.command_recv.try_recv() {
-                Ok(c) => Option::Some(c),
-                Err(e) => match e {
-                    TryRecvError::Disconnected => {
+            // handle all pending commands, so e.g. painting keeps up with a running simulation
+            loop {
+                match self.command_recv.try_recv() {
+                    Ok(c) => self.handle_command(c),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
                         println!("error command channel disconnected");
-                        Option::None
-                    }
-                    TryRecvError::Empty => Option::None,
-                },
+                        break;
+                    },
+                }
             }
         } else {
             // If the simulation is not running currently, this call to read a command
             // from the command channel will block.
-            command = match self.command_recv.recv() {
-                Ok(c) => Option::Some(c),
-                Err(_) => {
-                    println!("error command channel disconnected");
-                    Option::None
-                }
+            match self.command_recv.recv() {
+                Ok(c) => self.handle_command(c),
+                Err(_) => println!("error command channel disconnected"),
             }
         }
+    }
 
+    fn handle_command(&mut self, command: Command) {
         match command {
-            Some(c) => match c {
-                Command::Start => self.running = true,
-                Command::Stop => self.running = false,
-                Command::SingleStep => if !self.running { self.step(); },
-                Command::ChangeDelay(d) => self.delay = d,
-                Command::Reset(n, m) => {
-                    self.running = false;
-                    self.aut.reset(n, m);
+            Command::Start => self.running = true,
+            Command::Stop => self.running = false,
+            Command::SingleStep => if !self.running { self.step(); },
+            Command::ChangeDelay(d) => self.delay = d,
+            Command::Reset(n, m) => {
+                self.running = false;
+                self.aut.reset(n, m);
+                self.send_state();
+            },
+            Command::SetParam(name, value) => {
+                self.aut.set_param(&name, value);
+                if !self.running {
                     self.send_state();
-                },
+                }
+            },
+            Command::Paint(i, j, brush) => {
+                self.aut.paint(i, j, brush);
+                if !self.running {
+                    self.send_state();
+                }
             },
-            _ => {},
         }
     }
 
@@ -118,7 +138,7 @@ impl<T: Send, S: Send, U: Send> Simulation<T, S, U> {
         // send the initial state
         self.send_state();
         loop {
-            self.recv_command();
+            self.recv_commands();
             if self.running {
                 self.step();
                 thread::sleep(self.delay);
@@ -0,0 +1,45 @@
+// Distribution of positive sizes (e.g. of fires or avalanches) in logarithmic bins
+// [1], [2, 3], [4, 7], ..., [2^k, 2^(k+1) - 1].
+pub struct LogHistogram {
+    bins: Vec<usize>,
+    count: usize,
+    total: usize,
+    max: usize,
+}
+
+impl LogHistogram {
+    pub fn new() -> LogHistogram {
+        LogHistogram { bins: Vec::new(), count: 0, total: 0, max: 0 }
+    }
+
+    pub fn add(&mut self, size: usize) {
+        let bin = (usize::BITS - 1 - size.max(1).leading_zeros()) as usize;
+        if bin >= self.bins.len() {
+            self.bins.resize(bin + 1, 0);
+        }
+        self.bins[bin] += 1;
+        self.count += 1;
+        self.total += size;
+        self.max = self.max.max(size);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count > 0 { self.total as f64 / self.count as f64 } else { 0.0 }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    // Returns the number of values in every bin, named e.g. "<name> 4-7".
+    pub fn stats(&self, name: &str) -> Vec<(String, f64)> {
+        self.bins.iter().enumerate().map(|(b, c)| {
+            let label = if b == 0 { format!("{} 1", name) } else { format!("{} {}-{}", name, 1usize << b, (1usize << (b + 1)) - 1) };
+            (label, *c as f64)
+        }).collect()
+    }
+}